@@ -0,0 +1,65 @@
+use serde::Serialize;
+use serde::Deserialize;
+
+/// A groove template shifts the start of the lines of a track
+/// in time, for instance to play every second line a bit later
+/// for a swing feel. The offsets are given in fractions of a line,
+/// the offset in ticks is `offset * tpl`. The offsets repeat
+/// every `offsets.len()` lines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Groove {
+    pub name:    String,
+    /// Delay of the start of each line, 0.0 <= offset < 1.0.
+    pub offsets: Vec<f32>,
+}
+
+/// The biggest delay of a line, so that a line still starts
+/// before the line after it.
+const MAX_OFFSET : f32 = 0.95;
+
+impl Groove {
+    pub fn new(name: &str, offsets: Vec<f32>) -> Self {
+        Groove {
+            name: String::from(name),
+            offsets,
+        }
+    }
+
+    /// Creates a swing groove, that delays every second line
+    /// by `amount` of a line.
+    pub fn swing(name: &str, amount: f32) -> Self {
+        Self::new(name, vec![0.0, amount])
+    }
+
+    /// Returns the delay of the start of the line in fractions of a line.
+    pub fn offset(&self, line: usize) -> f64 {
+        if self.offsets.is_empty() { return 0.0; }
+
+        let o = self.offsets[line % self.offsets.len()];
+        (if o < 0.0 { 0.0 } else if o > MAX_OFFSET { MAX_OFFSET } else { o }) as f64
+    }
+}
+
+/// Maps the song position `pos` in lines (eg. ticks / tpl) to the line
+/// that plays at that position and the passed fraction of that line,
+/// taking the delays of `groove` into account.
+/// Returns None if the first line did not start yet.
+pub fn grooved_line_pos(groove: Option<&Groove>, pos: f64) -> Option<(usize, f64)> {
+    let line = pos.floor() as usize;
+    let g =
+        match groove {
+            Some(g) => g,
+            None    => return Some((line, pos - line as f64)),
+        };
+
+    let start = |l: usize| l as f64 + g.offset(l);
+
+    let line =
+        if start(line) <= pos { line }
+        else if line > 0      { line - 1 }
+        else                  { return None; };
+
+    let (a, b) = (start(line), start(line + 1));
+    let fract  = if b > a { (pos - a) / (b - a) } else { 0.0 };
+    Some((line, if fract > 1.0 { 1.0 } else { fract }))
+}
@@ -0,0 +1,32 @@
+use serde::Serialize;
+use serde::Deserialize;
+
+/// An instrument maps the note events of a track row to the signal
+/// groups that play them. The instrument number in the instrument column
+/// of a row selects the instrument, number 1 is the first instrument
+/// of the instrument table in the Tracker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Instrument {
+    pub name:   String,
+    /// The signal groups that receive the note on/off events.
+    pub groups: Vec<usize>,
+    /// Default parameters that are set when a note of this instrument
+    /// starts: (operator name, input name, value).
+    pub params: Vec<(String, String, f32)>,
+    /// The operator input (operator name, input name) that is set to
+    /// the frequency of the note in the tuning of the Tracker
+    /// when a note of this instrument starts.
+    #[serde(default)]
+    pub freq_input: Option<(String, String)>,
+}
+
+impl Instrument {
+    pub fn new(name: &str) -> Self {
+        Instrument {
+            name:   String::from(name),
+            groups: Vec::new(),
+            params: Vec::new(),
+            freq_input: None,
+        }
+    }
+}
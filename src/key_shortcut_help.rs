@@ -15,6 +15,62 @@ match page {
                       signal ops to `tracker.json` file.
     r               - Read contents of trackers and input values from
                       `tracker.json` again.
+    W / R           - Write/read the project to/from `tracker.txt`, in
+                      the compact text format.
+    m               - Import the notes and controllers of a MIDI file
+                      as new tracks. Enter the file name, optionally
+                      followed by the lines per pattern and the lines
+                      per beat (the quantization) and hit Return.
+    e               - Export the song to a MIDI file. Enter the file
+                      name, optionally followed by the controller number
+                      of the first value column (default 1) or `pb` for
+                      pitch bend, and hit Return.
+    i               - Import the channels of a ProTracker `.mod` or
+                      FastTracker `.xm` file as new tracks. Enter the
+                      file name and hit Return.
+    b               - Export the song to a compact binary file for
+                      intros, played by `SongPlayer`. Enter the file
+                      name and hit Return.
+    g               - Export the song as Rust source with constants,
+                      see `song_source::ConstSong`. Enter the file
+                      name and hit Return.
+    s               - Export the keys of the value columns as sync
+                      tracks for graphics, to `sync.json` and GNU Rocket
+                      `sync_<track>.track` files. Enter another base
+                      name than `sync` or just hit Return.
+    c               - Copy the block (see `v` in [Normal] mode) to
+                      `clipboard.txt` in the OpenMPT/ModPlug clipboard
+                      format.
+    p               - Paste the block in `clipboard.txt` at the cursor.
+
+[Track] Mode:
+    i               - Insert a new track right of the cursor track.
+                      Then select the kind of the track:
+                        m - Mixed: note, value and A/B columns
+                        n - Note: note and A/B columns
+                        a - Automation: value column only, enter
+                            the output range as `min max` and hit Return
+                        t - Trigger: trigger column, outputs a pulse,
+                            set a trigger with any [Note] mode key or
+                            its strength with the A value
+    k               - Change the kind of the cursor track, same keys as
+                      for inserting a track.
+    d               - Delete the cursor track.
+    h / l           - Move the cursor track to the left/right.
+    r               - Rename the cursor track, type the name and
+                      hit Return.
+    m               - Mute/unmute the cursor track.
+    s               - Solo/unsolo the cursor track.
+    f               - Switch whether muted tracks keep their last
+                      value or continue to interpolate. Muted trigger
+                      tracks always output 0.
+    + / -           - Add/remove a value column of the cursor track.
+    g               - Go to `Groove` mode for selecting the groove
+                      of the cursor track.
+    p               - Queue the pattern under the cursor, it is looped
+                      from the next bar on (shown as `>NN` and `@NN`).
+    a               - Queue going back to the arrangement.
+    c               - Cancel the queued launch.
 
 [Interpolation] Mode:
     s               - Step (no interpolation)
@@ -41,8 +97,43 @@ match page {
 [ScrollOps] Mode:
     h / j / k / l   - Scroll the signal groups / operators
 
-[A] / [B] Mode:
+[A] / [B] / [Instr] Mode:
     0-9 / A-F / a-f - Enter 2 hex digits
+
+[PatternLen] Mode:
+    0-9             - Enter the new number of lines of the pattern
+    Return          - Resize the pattern under the cursor. All places
+                      in the arrangement that use it change too.
+
+[ResampleLpp] Mode:
+    0-9             - Enter the new lines per pattern of the track
+    Return          - Resample all patterns of the current track, the
+                      rows are moved to their new line positions.
+
+[Condition] Mode:
+    p               - Play the row with a probability, enter the percent
+                      and hit Return.
+    e               - Play the row on the first and then every Nth pass
+                      through the song, enter N and hit Return.
+    1               - Play the row only on the first pass.
+    !               - Play the row on every pass except the first.
+    x               - Always play the row.
+
+[Slide] Mode:
+    0-9             - Enter the number of lines of the slide.
+    s / l / t / e   - Set the slide with step, linear, smooth or
+                      exponential interpolation. The note of the row
+                      does not start a new note, the pitch of the
+                      playing note slides to it instead. Note tracks
+                      output the pitch, the frequency input of the
+                      instrument (see `instrument`) follows it.
+    x               - Remove the slide.
+
+[Groove] Mode:
+    1-9             - Select the groove with that number, the grooves
+                      are defined with `groove` in `tracker.wl`.
+    0               - No groove. A track without groove uses the
+                      groove of the song.
 "#),
 _ => String::from(
 r#"
@@ -54,6 +145,7 @@ WDem Tracker - Keyboard Reference
 
 [Normal] Mode:
     h / l           - Move cursor to left/right track.
+    Shift + h / l   - Move cursor to the left/right value column.
     j / k           - Step cursor down/up a row.
     Shift + j / k   - Move cursor down/up exactly 1 row (regardless of the
                       step size).
@@ -61,6 +153,8 @@ WDem Tracker - Keyboard Reference
     x               - Delete contents of cursor cell.
     f               - Go to `File` mode, for writing/reading the
                       current contents of the tracks and input signals.
+    t               - Go to `Track` mode for inserting, deleting, moving
+                      and renaming tracks.
     y               - Refresh signal operator from background thread.
     i               - Go to `Interpolation` mode for setting the interpolation
                       of the current track.
@@ -73,6 +167,40 @@ WDem Tracker - Keyboard Reference
     n / m           - Stop the tracker and move the play cursor up/down a row.
     a               - Go to `A` mode for entering the A 8-bit hex value.
     b               - Go to `B` mode for entering the B 8-bit hex value.
+    I               - Go to `Instr` mode for entering the instrument number.
+    p               - Go to `PatternLen` mode for changing the number of
+                      lines of the pattern under the cursor.
+    r               - Go to `ResampleLpp` mode for changing the lines per
+                      pattern of the current track.
+    g               - Go to `Groove` mode for selecting the groove of
+                      the song.
+    R               - Switch record mode: off, replace, overdub. While
+                      recording, notes from the `Note` mode keys are
+                      written to the cursor track at the line nearest
+                      to the play position and sound immediately.
+                      Replace overwrites notes already in that line,
+                      overdub keeps them.
+    G               - Go to `Slide` mode, for sliding the pitch of the
+                      playing note to the note of the cursor row.
+    S               - Enter the key and scale of the project, like
+                      `C major`, `F# minor` or `Eb dorian` and hit
+                      Return. Scales: major, minor, harmonic, dorian,
+                      phrygian, lydian, mixolydian, locrian, pentatonic,
+                      minorpentatonic. Empty for no scale.
+                      Notes outside of the scale are shown in red.
+    N               - Switch whether entered notes snap to the scale.
+    v               - Set or remove the block mark. The block reaches
+                      from the mark to the cursor, without a mark the
+                      block is the cursor row. See `File` mode for copy
+                      and paste.
+    C / V           - Stamp a triad / seventh chord in the scale on the
+                      note under the cursor. The chord notes are written
+                      to the cursor track and the note tracks right of it.
+    A               - Switch whether the notes are played when the
+                      cursor moves over them. Entered notes are
+                      always played.
+    c               - Go to `Condition` mode for setting the condition
+                      when the note/trigger of the cursor row is played.
     - / . / 0-9     - For entering a value, just start typing the value
                       and hit Return or some other key.
 "#),
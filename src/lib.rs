@@ -2,6 +2,20 @@ pub mod gui_painter;
 pub mod ggez_gui_painter;
 pub mod tracker;
 pub mod track;
+pub mod pattern;
+pub mod instrument;
+pub mod groove;
+pub mod tuning;
+pub mod scale;
+pub mod midi;
+pub mod module_file;
+pub mod mpt_clipboard;
+pub mod project_text;
+pub mod song_blob;
+pub mod song_player;
+pub mod song_source;
+pub mod sync_track;
+pub mod sync_server;
 pub mod tracker_editor;
 pub mod scopes;
 pub mod vval_opin;
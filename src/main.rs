@@ -2,7 +2,18 @@ extern crate serde_json;
 extern crate ggez;
 
 use std::io::prelude::*;
+use serde::{Serialize, Deserialize};
 use wdem_tracker::track::*;
+use wdem_tracker::instrument::Instrument;
+use wdem_tracker::groove::Groove;
+use wdem_tracker::tuning::Tuning;
+use wdem_tracker::scale::Scale;
+use wdem_tracker::midi::{MidiFile, ValueOut, import_tracks, export_song};
+use wdem_tracker::module_file::import_module;
+use wdem_tracker::project_text::{project_to_text, text_to_project};
+use wdem_tracker::song_blob::export_song_blob;
+use wdem_tracker::song_source::song_to_rust;
+use wdem_tracker::sync_track::{SyncTracks, rocket_track_filename};
 use wdem_tracker::tracker::*;
 use wdem_tracker::tracker_editor::*;
 use wdem_tracker::scopes::{Scopes, SCOPE_SAMPLES, SCOPE_WIDTH};
@@ -57,6 +68,29 @@ use ggez::input::mouse::set_position;
 
 
 */
+/// The contents of the `tracker.json` project file.
+/// Older project files only contain the first two fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProjectFile {
+    #[serde(default)]
+    op_inputs:   Vec<(String, Vec<(String, OpIn)>)>,
+    tracks:      Vec<TrackSerialized>,
+    #[serde(default)]
+    instruments: Vec<Instrument>,
+    #[serde(default)]
+    grooves:     Vec<Groove>,
+    /// Index of the song groove.
+    #[serde(default)]
+    groove:      Option<usize>,
+    /// Seed of the random numbers for the row conditions.
+    #[serde(default)]
+    seed:        u64,
+    #[serde(default)]
+    tuning:      Tuning,
+    #[serde(default)]
+    scale:       Option<Scale>,
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 enum InputMode {
     Normal,
@@ -65,7 +99,25 @@ enum InputMode {
     Value,
     A,
     B,
+    Instr,
     Note,
+    PatternLen,
+    ResampleLpp,
+    TrackActions,
+    TrackName,
+    TrackKindSelect(bool),
+    AutomationRange(bool),
+    GrooveSelect(bool),
+    Condition,
+    ConditionNum(bool),
+    ScaleEntry,
+    MidiImport,
+    MidiExport,
+    ModuleImport,
+    BlobExport,
+    RustExport,
+    SyncExport,
+    Slide,
     OpInValue(usize, usize),
     FileActions,
     ScrollOps,
@@ -101,7 +153,126 @@ impl WDemTrackerGUI {
         let sync = ThreadTrackSync::new(sync_tx);
         let out = std::sync::Arc::new(std::sync::Mutex::new(TrackerThreadOutput::new()));
 
+        let trk = Rc::new(RefCell::new(Tracker::new(sync)));
+
         let genv = GlobalEnv::new_default();
+
+        let trk_instr = trk.clone();
+        genv.borrow_mut().add_func(
+            "instrument", move |env: &mut Env, _argc: usize| {
+                let num    = env.arg(0).i();
+                let name   = env.arg(1).s_raw();
+                let groups = env.arg(2);
+                let params = env.arg(3);
+                if num < 1 || num > 255 {
+                    return Ok(VVal::err_msg(
+                            &format!("bad instrument number: {}", num)));
+                }
+
+                let mut instr = Instrument::new(&name);
+
+                let mut i = 0;
+                while let Some(g) = groups.at(i) {
+                    instr.groups.push(g.i() as usize);
+                    i += 1;
+                }
+
+                let mut i = 0;
+                while let Some(p) = params.at(i) {
+                    instr.params.push((
+                        p.at(0).unwrap_or(VVal::Nul).s_raw(),
+                        p.at(1).unwrap_or(VVal::Nul).s_raw(),
+                        p.at(2).unwrap_or(VVal::Nul).f() as f32));
+                    i += 1;
+                }
+
+                let freq_input = env.arg(4);
+                if let (Some(op), Some(inp)) = (freq_input.at(0), freq_input.at(1)) {
+                    instr.freq_input = Some((op.s_raw(), inp.s_raw()));
+                }
+
+                trk_instr.borrow_mut().set_instrument(num as u8, instr);
+                Ok(VVal::Bol(true))
+            }, Some(4), Some(5));
+
+        let trk_tuning = trk.clone();
+        genv.borrow_mut().add_func(
+            "tuning", move |env: &mut Env, argc: usize| {
+                let read = |path: &str| {
+                    std::fs::read_to_string(path)
+                        .map_err(|e| format!("can't read '{}': {}", path, e))
+                };
+
+                let scl_path = env.arg(0).s_raw();
+                let tuning =
+                    read(&scl_path)
+                    .and_then(|scl| Tuning::from_scl(&scl))
+                    .and_then(|mut t| {
+                        if argc > 1 {
+                            let kbm_path = env.arg(1).s_raw();
+                            t.apply_kbm(&read(&kbm_path)?)?;
+                        }
+                        Ok(t)
+                    });
+
+                match tuning {
+                    Ok(t) => {
+                        trk_tuning.borrow_mut().set_tuning(t);
+                        Ok(VVal::Bol(true))
+                    },
+                    Err(e) => Ok(VVal::err_msg(&e)),
+                }
+            }, Some(1), Some(2));
+
+        let trk_groove = trk.clone();
+        genv.borrow_mut().add_func(
+            "groove", move |env: &mut Env, _argc: usize| {
+                let num     = env.arg(0).i();
+                let name    = env.arg(1).s_raw();
+                let offsets = env.arg(2);
+                if num < 1 {
+                    return Ok(VVal::err_msg(
+                            &format!("bad groove number: {}", num)));
+                }
+
+                let mut groove = Groove::new(&name, vec![]);
+
+                let mut i = 0;
+                while let Some(o) = offsets.at(i) {
+                    groove.offsets.push(o.f() as f32);
+                    i += 1;
+                }
+
+                trk_groove.borrow_mut().set_groove((num - 1) as usize, groove);
+                Ok(VVal::Bol(true))
+            }, Some(3), Some(3));
+
+        let trk_launch = trk.clone();
+        genv.borrow_mut().add_func(
+            "launch", move |env: &mut Env, _argc: usize| {
+                let track_idx = env.arg(0).i();
+                if track_idx < 0 {
+                    return Ok(VVal::err_msg(
+                            &format!("bad track index: {}", track_idx)));
+                }
+
+                let launch =
+                    match env.arg(1) {
+                        VVal::Nul => Launch::Arrangement,
+                        v         => Launch::Pattern(v.i() as usize),
+                    };
+                trk_launch.borrow_mut()
+                    .queue_launch(track_idx as usize, Some(launch));
+                Ok(VVal::Bol(true))
+            }, Some(2), Some(2));
+
+        let trk_seed = trk.clone();
+        genv.borrow_mut().add_func(
+            "condition_seed", move |env: &mut Env, _argc: usize| {
+                trk_seed.borrow_mut().set_seed(env.arg(0).i() as u64);
+                Ok(VVal::Bol(true))
+            }, Some(1), Some(1));
+
         let mut wl_eval_ctx =
             wlambda::compiler::EvalContext::new(genv);
 
@@ -125,7 +296,6 @@ impl WDemTrackerGUI {
         }
 
         let font = graphics::Font::new(ctx, "/DejaVuSansMono.ttf").unwrap();
-        let trk = Rc::new(RefCell::new(Tracker::new(sync)));
         let mut ctx = WDemTrackerGUI {
             tracker:            trk.clone(),
             editor:             TrackerEditor::new(trk),
@@ -178,8 +348,270 @@ impl WDemTrackerGUI {
     pub fn inp(&mut self, ti: TrackerInput) {
         self.editor.process_input(ti);
     }
+
+    /// Inserts a new track of the given kind or changes the
+    /// kind of the cursor track.
+    fn apply_track_kind(&mut self, insert: bool, kind: TrackKind) {
+        if insert {
+            self.inp(TrackerInput::InsertTrack(kind));
+        } else {
+            self.inp(TrackerInput::SetTrackKind(kind));
+        }
+    }
+
+    /// Lists the grooves that can be selected in the status line.
+    fn groove_select_text(&self) -> String {
+        let mut s = String::from("'0' none");
+        for (i, g) in self.tracker.borrow().grooves.iter().enumerate().take(9) {
+            s += &format!(", '{}' {}", i + 1, g.name);
+        }
+        s
+    }
+
+    fn project_file(&mut self) -> ProjectFile {
+        let tracker = self.tracker.borrow();
+        ProjectFile {
+            op_inputs:   self.op_inp_set.save_input_values(),
+            tracks:      tracker.serialize_tracks(),
+            instruments: tracker.instruments.clone(),
+            grooves:     tracker.grooves.clone(),
+            groove:      tracker.groove,
+            seed:        tracker.seed,
+            tuning:      tracker.tuning.clone(),
+            scale:       tracker.scale,
+        }
+    }
+
+    fn load_project_file(&mut self, v: ProjectFile) {
+        self.op_inp_set.load_input_values(&v.op_inputs);
+        let mut tracker = self.editor.tracker.borrow_mut();
+        for (i, groove) in v.grooves.into_iter().enumerate() {
+            tracker.set_groove(i, groove);
+        }
+        tracker.set_song_groove(v.groove);
+        tracker.set_seed(v.seed);
+        tracker.set_tuning(v.tuning);
+        tracker.scale = v.scale;
+        tracker.deserialize_tracks(v.tracks);
+        for (i, instr) in v.instruments.into_iter().enumerate() {
+            tracker.set_instrument((i + 1) as u8, instr);
+        }
+        drop(tracker);
+        self.op_inp_set.update();
+    }
+
+    /// Appends imported tracks, their names are made unique
+    /// by appending a number.
+    fn add_imported_tracks(&mut self, tracks: Vec<Track>) {
+        let mut tracker = self.tracker.borrow_mut();
+        for mut t in tracks.into_iter() {
+            let mut name = t.name.clone();
+            let mut i    = 2;
+            while tracker.tracks.iter().any(|tt| tt.name == name) {
+                name = format!("{}-{}", t.name, i);
+                i += 1;
+            }
+            t.name = name;
+            tracker.add_track(t);
+        }
+    }
+
+    /// Imports the tracks of a MIDI file. `args` is the file name,
+    /// optionally followed by the lines per pattern and lines per beat.
+    fn import_midi(&mut self, args: &str) {
+        let mut parts  = args.split_whitespace();
+        let filename   = match parts.next() { Some(f) => f.to_string(), None => return };
+        let (lpp, lpb) = {
+            let t = self.tracker.borrow();
+            (t.lpp, t.lpb)
+        };
+        let lpp = parts.next().and_then(|s| s.parse::<usize>().ok()).unwrap_or(lpp);
+        let lpb = parts.next().and_then(|s| s.parse::<usize>().ok()).unwrap_or(lpb);
+
+        let mf =
+            std::fs::read(&filename)
+                .map_err(|e| format!("read error '{}': {}", filename, e))
+                .and_then(|data| {
+                    MidiFile::parse(&data)
+                        .map_err(|e| format!("midi error '{}': {}", filename, e))
+                });
+
+        match mf {
+            Ok(mf) => {
+                let tracks = import_tracks(&mf, lpp, lpb);
+                let count  = tracks.len();
+                self.add_imported_tracks(tracks);
+
+                self.set_status_text(
+                    format!("imported {} tracks from '{}'", count, filename));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("MIDI IMPORT ERROR: {}", e);
+            },
+        }
+    }
+
+    /// Imports the channels of a ProTracker MOD or FastTracker XM file.
+    fn import_module_file(&mut self, filename: &str) {
+        let tracks =
+            std::fs::read(filename)
+                .map_err(|e| format!("read error '{}': {}", filename, e))
+                .and_then(|data| {
+                    import_module(&data)
+                        .map_err(|e| format!("module error '{}': {}", filename, e))
+                });
+
+        match tracks {
+            Ok(tracks) => {
+                let count = tracks.len();
+                self.add_imported_tracks(tracks);
+                self.set_status_text(
+                    format!("imported {} tracks from '{}'", count, filename));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("MODULE IMPORT ERROR: {}", e);
+            },
+        }
+    }
+
+    /// Exports the song to a MIDI file. `args` is the file name,
+    /// optionally followed by the first controller number for the value
+    /// columns or `pb` for sending the first value column as pitch bend.
+    fn export_midi(&mut self, args: &str) {
+        let mut parts = args.split_whitespace();
+        let filename  = match parts.next() { Some(f) => f.to_string(), None => return };
+        let values    =
+            match parts.next() {
+                Some("pb") => ValueOut::PitchBend,
+                Some(cc)   => ValueOut::Control(cc.parse::<u8>().unwrap_or(1)),
+                None       => ValueOut::Control(1),
+            };
+
+        let res = {
+            let t = self.tracker.borrow();
+            export_song(&t.tracks, t.lpb, t.tpl, t.tick_interval, values)
+        }.and_then(|(data, skipped)| {
+            std::fs::File::create(&filename)
+                .and_then(|mut file| file.write_all(&data))
+                .map(|()| skipped)
+                .map_err(|e| format!("write error '{}': {}", filename, e))
+        });
+
+        match res {
+            Ok(0) => {
+                self.set_status_text(format!("exported song to '{}'", filename));
+            },
+            Ok(skipped) => {
+                self.set_status_text(format!(
+                    "exported song to '{}', skipped {} notes above 127",
+                    filename, skipped));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("MIDI EXPORT ERROR: {}", e);
+            },
+        }
+    }
+
+    fn export_song_blob_file(&mut self, filename: &str) {
+        let op_inputs =
+            serde_json::to_value(self.op_inp_set.save_input_values())
+                .unwrap_or(serde_json::Value::Array(Vec::new()));
+
+        let res = {
+            let t = self.tracker.borrow();
+            export_song_blob(&t.tracks, t.lpb, t.tpl, t.tick_interval, t.groove, &op_inputs)
+        }.and_then(|data| {
+            std::fs::File::create(filename)
+                .and_then(|mut file| file.write_all(&data))
+                .map(|()| data.len())
+                .map_err(|e| format!("write error '{}': {}", filename, e))
+        });
+
+        match res {
+            Ok(len) => {
+                self.set_status_text(
+                    format!("exported song ({} bytes) to '{}'", len, filename));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("SONG BLOB EXPORT ERROR: {}", e);
+            },
+        }
+    }
+
+    fn export_rust_file(&mut self, filename: &str) {
+        let op_inputs =
+            serde_json::to_value(self.op_inp_set.save_input_values())
+                .unwrap_or(serde_json::Value::Array(Vec::new()));
+
+        let res =
+            song_to_rust(&*self.tracker.borrow(), &op_inputs)
+                .and_then(|src| {
+                    write_file_safely(filename, &src)
+                        .map_err(|e| format!("write error '{}': {}", filename, e))
+                });
+
+        match res {
+            Ok(()) => {
+                self.set_status_text(format!("exported song to '{}'", filename));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("RUST EXPORT ERROR: {}", e);
+            },
+        }
+    }
+
+    /// Writes the keys of the value columns to `base.json` and
+    /// to a GNU Rocket `.track` file per column.
+    fn export_sync_tracks(&mut self, base: &str) {
+        let sync = {
+            let t = self.tracker.borrow();
+            SyncTracks::from_tracks(&t.tracks, t.lpb, t.tpl, t.tick_interval)
+        };
+
+        let json_file = format!("{}.json", base);
+        let mut res =
+            serde_json::to_string_pretty(&sync)
+                .map_err(|e| format!("serialize error: {}", e))
+                .and_then(|s| {
+                    write_file_safely(&json_file, &s)
+                        .map_err(|e| format!("write error '{}': {}", json_file, e))
+                });
+
+        for st in sync.tracks.iter() {
+            if res.is_err() { break; }
+
+            let filename = rocket_track_filename(base, &st.name);
+            res =
+                std::fs::File::create(&filename)
+                    .and_then(|mut file| file.write_all(&st.to_rocket()))
+                    .map_err(|e| format!("write error '{}': {}", filename, e));
+        }
+
+        match res {
+            Ok(()) => {
+                self.set_status_text(
+                    format!("exported {} sync tracks to '{}'", sync.tracks.len(), json_file));
+            },
+            Err(e) => {
+                self.set_status_text(e.clone());
+                println!("SYNC EXPORT ERROR: {}", e);
+            },
+        }
+    }
 }
 
+/// The project file in the text format, see project_text.
+const TEXT_PROJECT_FILE : &str = "tracker.txt";
+
+/// The text file for exchanging blocks with other trackers, in the
+/// format of the OpenMPT/ModPlug clipboard.
+const CLIPBOARD_FILE : &str = "clipboard.txt";
+
 fn write_file_safely(filename: &str, s: &str) -> std::io::Result<()> {
     let tmpfile = format!("{}~", filename);
     let mut file = std::fs::File::create(tmpfile.clone())?;
@@ -194,7 +626,15 @@ impl EventHandler for WDemTrackerGUI {
     }
 
     fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
-        if keycode == KeyCode::Q {
+        if keycode == KeyCode::Q
+           && self.mode != InputMode::TrackName
+           && self.mode != InputMode::ScaleEntry
+           && self.mode != InputMode::MidiImport
+           && self.mode != InputMode::MidiExport
+           && self.mode != InputMode::ModuleImport
+           && self.mode != InputMode::BlobExport
+           && self.mode != InputMode::RustExport
+           && self.mode != InputMode::SyncExport {
             quit(ctx);
         } else if keycode == KeyCode::F1 {
             self.mode = InputMode::HelpScreen(0);
@@ -292,9 +732,73 @@ impl EventHandler for WDemTrackerGUI {
                     'h' => {
                         self.editor.process_input(TrackerInput::TrackLeft);
                     },
+                    't' => {
+                        self.mode = InputMode::TrackActions;
+                        self.set_status_text(
+                            format!("'i' insert, 'd' delete, 'h'/'l' move, 'r' rename, 'm' mute, 's' solo, 'f' freeze, 'g' groove, 'p'/'a'/'c' launch"));
+                    },
+                    'R' => {
+                        self.inp(TrackerInput::ToggleRecord);
+                        self.set_status_text(
+                            match self.editor.record {
+                                RecordMode::Off     => format!("record off"),
+                                RecordMode::Replace => format!("record (replace)"),
+                                RecordMode::Overdub => format!("record (overdub)"),
+                            });
+                    },
+                    'G' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::Slide;
+                        self.set_status_text(
+                            format!("slide lines[], then 's' step, 'l' linear, 't' smooth, 'e' exp, 'x' remove"));
+                    },
+                    'S' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::ScaleEntry;
+                        self.set_status_text(format!("scale (eg. 'C# minor')[]"));
+                    },
+                    'N' => {
+                        self.inp(TrackerInput::ToggleSnapToScale);
+                        self.set_status_text(
+                            if self.editor.snap_to_scale {
+                                format!("snap to scale on")
+                            } else {
+                                format!("snap to scale off")
+                            });
+                    },
+                    'v' => {
+                        self.inp(TrackerInput::ToggleBlockMark);
+                        self.set_status_text(
+                            if self.editor.block_marked() {
+                                format!("block mark set")
+                            } else {
+                                format!("block mark removed")
+                            });
+                    },
+                    'C' => { self.inp(TrackerInput::StampChord(3)); },
+                    'V' => { self.inp(TrackerInput::StampChord(4)); },
+                    'A' => {
+                        self.inp(TrackerInput::ToggleAuditionOnMove);
+                        self.set_status_text(
+                            if self.editor.audition_on_move {
+                                format!("audition on cursor move on")
+                            } else {
+                                format!("audition on cursor move off")
+                            });
+                    },
+                    'c' => {
+                        self.mode = InputMode::Condition;
+                        self.set_status_text(
+                            format!("'p' probability, 'e' every nth pass, '1' first pass, '!' not first, 'x' always"));
+                    },
+                    'g' => {
+                        self.mode = InputMode::GrooveSelect(false);
+                        self.set_status_text(self.groove_select_text());
+                    },
                     'f' => {
                         self.mode = InputMode::FileActions;
-                        self.set_status_text(format!("'w' write, 'r' read"));
+                        self.set_status_text(
+                            format!("'w' write, 'r' read, 'W' write text, 'R' read text, 'm' midi import, 'e' midi export, 'i' mod/xm import, 'b' song blob export, 'g' rust export, 's' sync tracks, 'c' copy block, 'p' paste block"));
                     },
                     'y' => {
                         self.op_inp_set.update();
@@ -316,6 +820,12 @@ impl EventHandler for WDemTrackerGUI {
                     'l' => {
                         self.editor.process_input(TrackerInput::TrackRight);
                     },
+                    'H' => {
+                        self.editor.process_input(TrackerInput::ValueColumnLeft);
+                    },
+                    'L' => {
+                        self.editor.process_input(TrackerInput::ValueColumnRight);
+                    },
                     'i' => {
                         self.mode = InputMode::Interpolation;
                     },
@@ -346,6 +856,20 @@ impl EventHandler for WDemTrackerGUI {
                         self.num_txt = String::from("");
                         self.mode = InputMode::B;
                     },
+                    'I' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::Instr;
+                    },
+                    'p' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::PatternLen;
+                        self.set_status_text(format!("pattern len[]"));
+                    },
+                    'r' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::ResampleLpp;
+                        self.set_status_text(format!("lines per pattern[]"));
+                    },
                     '-' | '.' | '0'..='9' => {
                         self.num_txt = String::from("");
                         self.num_txt.push(character);
@@ -399,7 +923,9 @@ impl EventHandler for WDemTrackerGUI {
 
                 if note > 0 {
                     self.inp(TrackerInput::SetNote(note));
-                    self.editor.process_input(TrackerInput::StepDown);
+                    if self.editor.record == RecordMode::Off {
+                        self.editor.process_input(TrackerInput::StepDown);
+                    }
                 }
             },
             InputMode::A => {
@@ -432,6 +958,21 @@ impl EventHandler for WDemTrackerGUI {
                     self.mode = InputMode::Normal;
                 }
             },
+            InputMode::Instr => {
+                match character {
+                    '0'..='9' | 'A'..='F' | 'a'..='f'  => {
+                        self.num_txt.push(character);
+                        self.set_status_text(format!("instr[{}]", self.num_txt));
+                    },
+                    _ => { }
+                }
+
+                if self.num_txt.len() >= 2 {
+                    self.inp(TrackerInput::SetInstr(
+                        u8::from_str_radix(&self.num_txt, 16).unwrap_or(0)));
+                    self.mode = InputMode::Normal;
+                }
+            },
             InputMode::OpInValue(op_idx, in_idx) => {
                 match character {
                     '-' | '.' | '0'..='9' => {
@@ -463,6 +1004,38 @@ impl EventHandler for WDemTrackerGUI {
 
                 self.set_status_text(format!("value[{}]", self.num_txt));
             },
+            InputMode::PatternLen => {
+                match character {
+                    '0'..='9' => {
+                        self.num_txt.push(character);
+                    },
+                    '\r' => {
+                        if let Ok(len) = self.num_txt.parse::<usize>() {
+                            self.inp(TrackerInput::SetPatternLen(len));
+                        }
+                        self.mode = InputMode::Normal;
+                    },
+                    _ => { }
+                }
+
+                self.set_status_text(format!("pattern len[{}]", self.num_txt));
+            },
+            InputMode::ResampleLpp => {
+                match character {
+                    '0'..='9' => {
+                        self.num_txt.push(character);
+                    },
+                    '\r' => {
+                        if let Ok(lpp) = self.num_txt.parse::<usize>() {
+                            self.inp(TrackerInput::ResampleTrackLpp(lpp));
+                        }
+                        self.mode = InputMode::Normal;
+                    },
+                    _ => { }
+                }
+
+                self.set_status_text(format!("lines per pattern[{}]", self.num_txt));
+            },
             InputMode::Interpolation => {
                 match character {
                     'e' => { self.inp(TrackerInput::SetInterpExp); },
@@ -497,10 +1070,9 @@ impl EventHandler for WDemTrackerGUI {
             InputMode::FileActions => {
                 match character {
                     'w' => {
-                        let s  = self.op_inp_set.save_input_values();
-                        let st = self.editor.tracker.borrow().serialize_tracks();
+                        let project = self.project_file();
 
-                        match serde_json::to_string_pretty(&(s, st)) {
+                        match serde_json::to_string_pretty(&project) {
                             Ok(s) => {
                                 match write_file_safely("tracker.json", &s) {
                                     Ok(()) => {
@@ -528,10 +1100,7 @@ impl EventHandler for WDemTrackerGUI {
                                     Ok(_) => {
                                         match serde_json::from_str(&c) {
                                             Ok(v) => {
-                                                let v : (Vec<(String, Vec<(String, OpIn)>)>, Vec<TrackSerialized>) = v;
-                                                self.op_inp_set.load_input_values(&v.0);
-                                                self.editor.tracker.borrow_mut().deserialize_tracks(v.1);
-                                                self.op_inp_set.update();
+                                                self.load_project_file(v);
                                             },
                                             Err(e) => {
                                                 self.set_status_text(
@@ -555,11 +1124,433 @@ impl EventHandler for WDemTrackerGUI {
                         }
 //        valmap = serde_json::from_str(s).unwrap_or(valmap);
                     },
+                    'W' => {
+                        let project = self.project_file();
+                        let res =
+                            serde_json::to_value(&project)
+                                .map_err(|e| format!("serialize error: {}", e))
+                                .and_then(|v| {
+                                    let mut v =
+                                        match v {
+                                            serde_json::Value::Object(m) => m,
+                                            _ => serde_json::Map::new(),
+                                        };
+                                    v.remove("tracks");
+
+                                    write_file_safely(
+                                        TEXT_PROJECT_FILE,
+                                        &project_to_text(&v, &project.tracks))
+                                    .map_err(|e| format!(
+                                        "write error '{}': {}", TEXT_PROJECT_FILE, e))
+                                });
+
+                        match res {
+                            Ok(()) => {
+                                self.set_status_text(
+                                    format!("everything written ok to '{}'", TEXT_PROJECT_FILE));
+                            },
+                            Err(e) => {
+                                self.set_status_text(e.clone());
+                                println!("{} WRITE ERROR: {}", TEXT_PROJECT_FILE, e);
+                            }
+                        }
+                    },
+                    'R' => {
+                        let res =
+                            std::fs::read_to_string(TEXT_PROJECT_FILE)
+                                .map_err(|e| format!("read error '{}': {}", TEXT_PROJECT_FILE, e))
+                                .and_then(|text| text_to_project(&text))
+                                .and_then(|(mut v, tracks)| {
+                                    v.insert(
+                                        String::from("tracks"),
+                                        serde_json::to_value(tracks)
+                                            .map_err(|e| format!("{}", e))?);
+
+                                    serde_json::from_value::<ProjectFile>(
+                                        serde_json::Value::Object(v))
+                                    .map_err(|e| format!(
+                                        "deserialize error '{}': {}", TEXT_PROJECT_FILE, e))
+                                });
+
+                        match res {
+                            Ok(v) => { self.load_project_file(v); },
+                            Err(e) => {
+                                self.set_status_text(e.clone());
+                                println!("{} READ ERROR: {}", TEXT_PROJECT_FILE, e);
+                            }
+                        }
+                    },
+                    'm' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::MidiImport;
+                        self.set_status_text(format!("midi file [lpp] [lpb][]"));
+                        return;
+                    },
+                    'e' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::MidiExport;
+                        self.set_status_text(format!("midi file [cc|pb][]"));
+                        return;
+                    },
+                    'c' => {
+                        let text = self.editor.copy_block();
+                        match write_file_safely(CLIPBOARD_FILE, &text) {
+                            Ok(()) => {
+                                self.set_status_text(
+                                    format!("block copied to '{}'", CLIPBOARD_FILE));
+                            },
+                            Err(e) => {
+                                self.set_status_text(
+                                    format!("write error '{}': {}", CLIPBOARD_FILE, e));
+                                println!("{} WRITE ERROR: {}", CLIPBOARD_FILE, e);
+                            }
+                        }
+                    },
+                    'p' => {
+                        let res =
+                            std::fs::read_to_string(CLIPBOARD_FILE)
+                                .map_err(|e| format!("read error '{}': {}", CLIPBOARD_FILE, e))
+                                .and_then(|text| self.editor.paste_block(&text));
+                        match res {
+                            Ok(n) => {
+                                self.set_status_text(
+                                    format!("pasted {} channels from '{}'", n, CLIPBOARD_FILE));
+                            },
+                            Err(e) => {
+                                self.set_status_text(e.clone());
+                                println!("PASTE ERROR: {}", e);
+                            }
+                        }
+                    },
+                    'i' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::ModuleImport;
+                        self.set_status_text(format!("mod/xm file[]"));
+                        return;
+                    },
+                    'b' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::BlobExport;
+                        self.set_status_text(format!("song blob file[]"));
+                        return;
+                    },
+                    'g' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::RustExport;
+                        self.set_status_text(format!("rust file[]"));
+                        return;
+                    },
+                    's' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::SyncExport;
+                        self.set_status_text(format!("sync base name (sync)[]"));
+                        return;
+                    },
+                    _ => (),
+                }
+
+                self.mode = InputMode::Normal;
+            },
+            InputMode::TrackActions => {
+                match character {
+                    'i' | 'k' => {
+                        self.mode = InputMode::TrackKindSelect(character == 'i');
+                        self.set_status_text(
+                            format!("'m' mixed, 'n' note, 'a' automation, 't' trigger"));
+                        return;
+                    },
+                    'd' => { self.inp(TrackerInput::DeleteTrack); },
+                    'h' => { self.inp(TrackerInput::MoveTrackLeft); },
+                    'l' => { self.inp(TrackerInput::MoveTrackRight); },
+                    'm' => { self.inp(TrackerInput::ToggleMute); },
+                    's' => { self.inp(TrackerInput::ToggleSolo); },
+                    'f' => { self.inp(TrackerInput::ToggleFreezeMutedValues); },
+                    '+' => { self.inp(TrackerInput::AddValueColumn); },
+                    '-' => { self.inp(TrackerInput::RemoveValueColumn); },
+                    'p' => { self.inp(TrackerInput::QueueCursorPattern); },
+                    'a' => { self.inp(TrackerInput::QueueLaunch(Some(Launch::Arrangement))); },
+                    'c' => { self.inp(TrackerInput::QueueLaunch(None)); },
+                    'g' => {
+                        self.mode = InputMode::GrooveSelect(true);
+                        self.set_status_text(self.groove_select_text());
+                        return;
+                    },
+                    'r' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::TrackName;
+                        self.set_status_text(format!("track name[]"));
+                        return;
+                    },
+                    _ => (),
+                }
+
+                self.mode = InputMode::Normal;
+            },
+            InputMode::TrackName => {
+                match character {
+                    '\r' => {
+                        if !self.num_txt.is_empty() {
+                            let name = self.num_txt.clone();
+                            self.editor.rename_current_track(&name);
+                        }
+                        self.mode = InputMode::Normal;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(format!("track name[{}]", self.num_txt));
+            },
+            InputMode::TrackKindSelect(insert) => {
+                match character {
+                    'm' => { self.apply_track_kind(insert, TrackKind::Mixed); },
+                    'n' => { self.apply_track_kind(insert, TrackKind::Note); },
+                    't' => { self.apply_track_kind(insert, TrackKind::Trigger); },
+                    'a' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::AutomationRange(insert);
+                        self.set_status_text(format!("range min max[]"));
+                        return;
+                    },
+                    _ => (),
+                }
+
+                self.mode = InputMode::Normal;
+            },
+            InputMode::AutomationRange(insert) => {
+                match character {
+                    '-' | '.' | ' ' | '0'..='9' => {
+                        self.num_txt.push(character);
+                    },
+                    '\r' => {
+                        let range : Vec<f32> =
+                            self.num_txt
+                                .split_whitespace()
+                                .map(|s| s.parse::<f32>().unwrap_or(0.0))
+                                .collect();
+                        let (min, max) =
+                            if range.len() == 2 && range[0] < range[1] {
+                                (range[0], range[1])
+                            } else {
+                                (0.0, 1.0)
+                            };
+                        self.apply_track_kind(
+                            insert, TrackKind::Automation { min, max });
+                        self.mode = InputMode::Normal;
+                    },
+                    _ => { }
+                }
+
+                self.set_status_text(format!("range min max[{}]", self.num_txt));
+            },
+            InputMode::ScaleEntry => {
+                match character {
+                    '\r' => {
+                        let scale = Scale::parse(&self.num_txt);
+                        self.editor.tracker.borrow_mut().scale = scale;
+                        self.mode = InputMode::Normal;
+                        self.set_status_text(
+                            match scale {
+                                Some(s) => format!("scale: {}", s.name()),
+                                None    => format!("no scale"),
+                            });
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("scale (eg. 'C# minor')[{}]", self.num_txt));
+            },
+            InputMode::MidiImport => {
+                match character {
+                    '\r' => {
+                        let args = self.num_txt.clone();
+                        self.mode = InputMode::Normal;
+                        self.import_midi(&args);
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("midi file [lpp] [lpb][{}]", self.num_txt));
+            },
+            InputMode::MidiExport => {
+                match character {
+                    '\r' => {
+                        let args = self.num_txt.clone();
+                        self.mode = InputMode::Normal;
+                        self.export_midi(&args);
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("midi file [cc|pb][{}]", self.num_txt));
+            },
+            InputMode::ModuleImport => {
+                match character {
+                    '\r' => {
+                        let filename = self.num_txt.trim().to_string();
+                        self.mode = InputMode::Normal;
+                        if !filename.is_empty() {
+                            self.import_module_file(&filename);
+                        }
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("mod/xm file[{}]", self.num_txt));
+            },
+            InputMode::BlobExport => {
+                match character {
+                    '\r' => {
+                        let filename = self.num_txt.trim().to_string();
+                        self.mode = InputMode::Normal;
+                        if !filename.is_empty() {
+                            self.export_song_blob_file(&filename);
+                        }
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("song blob file[{}]", self.num_txt));
+            },
+            InputMode::RustExport => {
+                match character {
+                    '\r' => {
+                        let filename = self.num_txt.trim().to_string();
+                        self.mode = InputMode::Normal;
+                        if !filename.is_empty() {
+                            self.export_rust_file(&filename);
+                        }
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("rust file[{}]", self.num_txt));
+            },
+            InputMode::SyncExport => {
+                match character {
+                    '\r' => {
+                        let base = self.num_txt.trim().to_string();
+                        let base = if base.is_empty() { String::from("sync") } else { base };
+                        self.mode = InputMode::Normal;
+                        self.export_sync_tracks(&base);
+                        return;
+                    },
+                    '\u{8}' => { self.num_txt.pop(); },
+                    c if !c.is_control() => { self.num_txt.push(c); },
+                    _ => { }
+                }
+
+                self.set_status_text(
+                    format!("sync base name (sync)[{}]", self.num_txt));
+            },
+            InputMode::Slide => {
+                let int =
+                    match character {
+                        '0'..='9' => {
+                            self.num_txt.push(character);
+                            self.set_status_text(
+                                format!("slide lines[{}]", self.num_txt));
+                            return;
+                        },
+                        's' => Some(Interpolation::Step),
+                        'l' => Some(Interpolation::Lerp),
+                        't' => Some(Interpolation::SStep),
+                        'e' => Some(Interpolation::Exp),
+                        _   => None,
+                    };
+
+                let lines = self.num_txt.parse::<u8>().unwrap_or(1);
+                let lines = if lines < 1 { 1 } else { lines };
+                match (character, int) {
+                    ('x', _)      => self.inp(TrackerInput::SetSlide(None)),
+                    (_, Some(int)) => self.inp(TrackerInput::SetSlide(Some((lines, int)))),
+                    _ => (),
+                }
+
+                self.mode = InputMode::Normal;
+            },
+            InputMode::Condition => {
+                match character {
+                    'p' | 'e' => {
+                        self.num_txt = String::from("");
+                        self.mode = InputMode::ConditionNum(character == 'p');
+                        self.set_status_text(
+                            if character == 'p' { format!("probability %[]") }
+                            else                { format!("every nth pass[]") });
+                        return;
+                    },
+                    '1' => { self.inp(TrackerInput::SetCondition(Condition::FirstPass)); },
+                    '!' => { self.inp(TrackerInput::SetCondition(Condition::NotFirstPass)); },
+                    'x' => { self.inp(TrackerInput::SetCondition(Condition::Always)); },
                     _ => (),
                 }
 
                 self.mode = InputMode::Normal;
             },
+            InputMode::ConditionNum(probability) => {
+                match character {
+                    '0'..='9' => {
+                        self.num_txt.push(character);
+                    },
+                    '\r' => {
+                        if let Ok(n) = self.num_txt.parse::<u8>() {
+                            let cond =
+                                if probability {
+                                    Condition::Probability(if n > 100 { 100 } else { n })
+                                } else {
+                                    Condition::EveryNth(n)
+                                };
+                            self.inp(TrackerInput::SetCondition(cond));
+                        }
+                        self.mode = InputMode::Normal;
+                    },
+                    _ => { }
+                }
+
+                if probability {
+                    self.set_status_text(format!("probability %[{}]", self.num_txt));
+                } else {
+                    self.set_status_text(format!("every nth pass[{}]", self.num_txt));
+                }
+            },
+            InputMode::GrooveSelect(track) => {
+                if let Some(num) = character.to_digit(10) {
+                    let groove_idx =
+                        if num == 0 { None } else { Some((num - 1) as usize) };
+                    if track {
+                        self.inp(TrackerInput::SetTrackGroove(groove_idx));
+                    } else {
+                        self.inp(TrackerInput::SetSongGroove(groove_idx));
+                    }
+                }
+
+                self.mode = InputMode::Normal;
+            },
             InputMode::ScrollOps => {
                 match character {
                     'h' => {
@@ -612,6 +1603,14 @@ impl EventHandler for WDemTrackerGUI {
             if let Ok(mut out) = self.tracker_thread_out.lock() {
                 play_line = out.pos;
                 cpu       = out.cpu;
+                for (track_idx, pat_idx) in out.launches.drain(..) {
+                    self.tracker.borrow_mut()
+                        .set_launched_pattern(track_idx, pat_idx);
+                }
+                for (track_idx, line, note) in out.recorded.drain(..) {
+                    self.tracker.borrow_mut()
+                        .set_recorded_note(track_idx, line, note);
+                }
                 if out.audio_scope_done {
                     self.audio_scopes.update_from_audio_bufs(
                         &out.audio_scope_samples);
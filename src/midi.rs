@@ -0,0 +1,543 @@
+use crate::track::*;
+use std::collections::BTreeMap;
+
+/// The events of a MIDI track that are used by the tracker,
+/// all other events are skipped when reading a file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MidiEventKind {
+    /// Note and velocity, a note on with velocity 0 is read as note off.
+    NoteOn(u8, u8),
+    NoteOff(u8),
+    /// Controller number and value.
+    Control(u8, u8),
+    /// Pitch bend value, 0x2000 is the center.
+    PitchBend(u16),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MidiEvent {
+    /// Absolute time in MIDI ticks.
+    pub tick:    u64,
+    pub channel: u8,
+    pub kind:    MidiEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiTrack {
+    pub name:   String,
+    pub events: Vec<MidiEvent>,
+}
+
+/// The contents of a Standard MIDI File.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiFile {
+    pub format:   u16,
+    /// MIDI ticks per quarter note.
+    pub division: u16,
+    /// The first tempo in microseconds per quarter note.
+    pub tempo:    Option<u32>,
+    pub tracks:   Vec<MidiTrack>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self { Reader { data, pos: 0 } }
+
+    fn is_end(&self) -> bool { self.pos >= self.data.len() }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.data.len() {
+            return Err(format!("unexpected end of data at {}", self.pos));
+        }
+        let b = &self.data[self.pos..(self.pos + n)];
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> { Ok(self.bytes(1)?[0]) }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(  ((b[0] as u32) << 24) | ((b[1] as u32) << 16)
+           | ((b[2] as u32) << 8)  |   b[3] as u32)
+    }
+
+    /// Reads a variable length quantity.
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut v : u32 = 0;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            v = (v << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 { return Ok(v); }
+        }
+        Err(format!("bad variable length quantity at {}", self.pos))
+    }
+}
+
+fn parse_track(data: &[u8], tempo: &mut Option<u32>) -> Result<MidiTrack, String> {
+    let mut r       = Reader::new(data);
+    let mut tick    = 0;
+    let mut status  = 0;
+    let mut track   = MidiTrack { name: String::from(""), events: Vec::new() };
+
+    while !r.is_end() {
+        tick += r.vlq()? as u64;
+
+        let mut b = r.u8()?;
+        if b < 0x80 {
+            // running status, the byte is the first data byte
+            if status == 0 {
+                return Err(String::from("data byte without status"));
+            }
+            r.pos -= 1;
+            b = status;
+        }
+
+        match b {
+            0xFF => {
+                let typ = r.u8()?;
+                let len = r.vlq()? as usize;
+                let d   = r.bytes(len)?;
+                match typ {
+                    0x03 if track.name.is_empty() => {
+                        track.name = String::from_utf8_lossy(d).to_string();
+                    },
+                    0x51 if len == 3 && tempo.is_none() => {
+                        *tempo = Some(  ((d[0] as u32) << 16)
+                                      | ((d[1] as u32) << 8)
+                                      |   d[2] as u32);
+                    },
+                    0x2F => break,
+                    _ => (),
+                }
+            },
+            0xF0 | 0xF7 => {
+                let len = r.vlq()? as usize;
+                r.bytes(len)?;
+            },
+            _ => {
+                status = b;
+                let channel = b & 0x0F;
+                let kind =
+                    match b & 0xF0 {
+                        0x80 => { let n = r.u8()?; r.u8()?; Some(MidiEventKind::NoteOff(n)) },
+                        0x90 => {
+                            let n = r.u8()?;
+                            let v = r.u8()?;
+                            if v == 0 { Some(MidiEventKind::NoteOff(n)) }
+                            else      { Some(MidiEventKind::NoteOn(n, v)) }
+                        },
+                        0xB0 => {
+                            let c = r.u8()?;
+                            let v = r.u8()?;
+                            Some(MidiEventKind::Control(c, v))
+                        },
+                        0xE0 => {
+                            let l = r.u8()? as u16;
+                            let h = r.u8()? as u16;
+                            Some(MidiEventKind::PitchBend((h << 7) | l))
+                        },
+                        0xA0 => { r.bytes(2)?; None },
+                        0xC0 | 0xD0 => { r.u8()?; None },
+                        _ => return Err(format!("bad status byte {:02X}", b)),
+                    };
+
+                if let Some(kind) = kind {
+                    track.events.push(MidiEvent { tick, channel, kind });
+                }
+            },
+        }
+    }
+
+    Ok(track)
+}
+
+impl MidiFile {
+    /// Parses the contents of a Standard MIDI File.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut r = Reader::new(data);
+
+        if r.bytes(4)? != b"MThd" {
+            return Err(String::from("not a MIDI file"));
+        }
+        let hdr_len  = r.u32()? as usize;
+        let hdr      = r.bytes(hdr_len)?;
+        if hdr_len < 6 {
+            return Err(String::from("bad MIDI header"));
+        }
+        let format   = ((hdr[0] as u16) << 8) | hdr[1] as u16;
+        let division = ((hdr[4] as u16) << 8) | hdr[5] as u16;
+        if division & 0x8000 != 0 {
+            return Err(String::from("SMPTE time division is not supported"));
+        }
+
+        let mut tempo  = None;
+        let mut tracks = Vec::new();
+        while !r.is_end() {
+            let id  = r.bytes(4)?;
+            let len = r.u32()? as usize;
+            let d   = r.bytes(len)?;
+            if id == b"MTrk" {
+                tracks.push(parse_track(d, &mut tempo)?);
+            }
+        }
+
+        Ok(MidiFile { format, division, tempo, tracks })
+    }
+}
+
+/// A track that is filled while importing, one for each voice
+/// of a MIDI channel.
+struct Voice {
+    track:   Track,
+    /// The playing note and the line it started at.
+    playing: Option<(u8, usize)>,
+}
+
+/// Converts the notes and controllers of a MIDI file to tracks.
+/// Each channel of each MIDI track becomes a track, overlapping notes
+/// are put into additional tracks. The events are quantized to
+/// `lpb` lines per quarter note, the tracks have `lpp` lines per pattern.
+/// The controllers of a channel become value columns of its first track,
+/// their values are mapped from 0-127 to 0.0-1.0.
+pub fn import_tracks(mf: &MidiFile, lpp: usize, lpb: usize) -> Vec<Track> {
+    let lpp      = if lpp < 1 { 1 } else { lpp };
+    let division = if mf.division < 1 { 1 } else { mf.division } as f64;
+    let tick2line = |tick: u64| {
+        ((tick as f64 * lpb as f64) / division).round() as usize
+    };
+
+    let mut tracks = Vec::new();
+
+    for (midi_idx, mt) in mf.tracks.iter().enumerate() {
+        let mut channels : BTreeMap<u8, Vec<&MidiEvent>> = BTreeMap::new();
+        for e in mt.events.iter() {
+            channels.entry(e.channel).or_insert_with(Vec::new).push(e);
+        }
+
+        let base_name =
+            if mt.name.is_empty() { format!("midi{}", midi_idx) }
+            else                  { mt.name.clone() };
+
+        for (channel, events) in channels.iter() {
+            let mut ccs : Vec<u8> =
+                events.iter().filter_map(|e| {
+                    match e.kind {
+                        MidiEventKind::Control(c, _) => Some(c),
+                        _ => None,
+                    }
+                }).collect();
+            ccs.sort();
+            ccs.dedup();
+
+            let has_notes =
+                events.iter().any(|e| {
+                    match e.kind { MidiEventKind::NoteOn(_, _) => true, _ => false }
+                });
+            if !has_notes && ccs.is_empty() { continue; }
+
+            let name =
+                if channels.len() > 1 { format!("{}c{}", base_name, channel + 1) }
+                else                  { base_name.clone() };
+
+            let mut first =
+                if ccs.is_empty() { Track::new_kind(&name, lpp, TrackKind::Note) }
+                else              { Track::new_kind(&name, lpp, TrackKind::Mixed) };
+            first.set_value_columns(if ccs.is_empty() { 1 } else { ccs.len() });
+
+            let mut voices = vec![Voice { track: first, playing: None }];
+
+            for e in events.iter() {
+                let line = tick2line(e.tick);
+
+                match e.kind {
+                    MidiEventKind::NoteOn(note, _) => {
+                        if note < 2 { continue; }
+
+                        let idx =
+                            match voices.iter().position(|v| v.playing.is_none()) {
+                                Some(idx) => idx,
+                                None => {
+                                    let vname = format!("{}.{}", name, voices.len() + 1);
+                                    voices.push(Voice {
+                                        track: Track::new_kind(&vname, lpp, TrackKind::Note),
+                                        playing: None,
+                                    });
+                                    voices.len() - 1
+                                },
+                            };
+
+                        voices[idx].track.set_note(line, note);
+                        voices[idx].playing = Some((note, line));
+                    },
+                    MidiEventKind::NoteOff(note) => {
+                        let v =
+                            voices.iter_mut().find(|v| {
+                                v.playing.map(|(n, _)| n == note).unwrap_or(false)
+                            });
+
+                        if let Some(v) = v {
+                            let start = v.playing.map(|(_, l)| l).unwrap_or(0);
+                            v.playing = None;
+
+                            // A note lasts at least one line:
+                            let line = if line <= start { start + 1 } else { line };
+                            let free =
+                                v.track.row_checked(line)
+                                    .map(|(_, r)| r.note == 0)
+                                    .unwrap_or(true);
+                            if free {
+                                v.track.set_note(line, 1);
+                            }
+                        }
+                    },
+                    MidiEventKind::Control(c, val) => {
+                        if let Some(col) = ccs.iter().position(|cc| *cc == c) {
+                            let t = &mut voices[0].track;
+                            t.set_value(col, line, val as f32 / 127.0);
+                            t.set_int(col, line, Interpolation::Lerp);
+                        }
+                    },
+                    MidiEventKind::PitchBend(_) => (),
+                }
+            }
+
+            for v in voices.into_iter() {
+                tracks.push(v.track);
+            }
+        }
+    }
+
+    tracks
+}
+
+/// Where the value columns of the tracks are sent to by export_song().
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ValueOut {
+    /// The value columns are sent as the controller with this number
+    /// and the following controllers.
+    Control(u8),
+    /// The first value column is sent as pitch bend, the following
+    /// columns as the controllers starting with 1.
+    PitchBend,
+}
+
+const EXPORT_VELOCITY : u8 = 100;
+/// The note of the exported trigger pulses.
+const TRIGGER_NOTE    : u8 = 60;
+/// The highest controller for the value columns, the
+/// controllers from 120 on are channel mode messages.
+const MAX_EXPORT_CC   : usize = 119;
+
+fn push_vlq(out: &mut Vec<u8>, v: u64) {
+    let mut bytes = vec![(v & 0x7F) as u8];
+    let mut v = v >> 7;
+    while v > 0 {
+        bytes.push(((v & 0x7F) as u8) | 0x80);
+        v >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.push((v >> 8) as u8);
+    out.push(v as u8);
+}
+
+fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.push((v >> 24) as u8);
+    out.push((v >> 16) as u8);
+    out.push((v >> 8) as u8);
+    out.push(v as u8);
+}
+
+/// Collects the events of a MIDI track with their absolute time.
+struct TrackWriter {
+    data:      Vec<u8>,
+    last_tick: u64,
+}
+
+impl TrackWriter {
+    fn new() -> Self { TrackWriter { data: Vec::new(), last_tick: 0 } }
+
+    fn event(&mut self, tick: u64, bytes: &[u8]) {
+        let tick = if tick < self.last_tick { self.last_tick } else { tick };
+        push_vlq(&mut self.data, tick - self.last_tick);
+        self.data.extend_from_slice(bytes);
+        self.last_tick = tick;
+    }
+
+    fn meta(&mut self, tick: u64, typ: u8, d: &[u8]) {
+        let mut bytes = vec![0xFF, typ];
+        push_vlq(&mut bytes, d.len() as u64);
+        bytes.extend_from_slice(d);
+        self.event(tick, &bytes);
+    }
+
+    fn write_chunk(mut self, end_tick: u64, out: &mut Vec<u8>) {
+        self.meta(end_tick, 0x2F, &[]);
+        out.extend_from_slice(b"MTrk");
+        push_u32(out, self.data.len() as u32);
+        out.extend_from_slice(&self.data);
+    }
+}
+
+/// Writes the events of one track, returns the MIDI tick the track
+/// ends at and the number of notes that are above the MIDI note range.
+fn export_track(t: &Track, channel: u8, tpl: usize, values: ValueOut, w: &mut TrackWriter) -> (u64, usize) {
+    let mut t = t.clone();
+    t.desync();
+    t.reset_play_line();
+
+    let tpl          = tpl as u64;
+    let cols         = if t.kind.has_values() { t.value_columns } else { 0 };
+    let mut last_out = vec![None; cols];
+    let mut playing  : Option<u8> = None;
+    let mut skipped  = 0;
+
+    w.meta(0, 0x03, t.name.as_bytes());
+
+    for line in 0..t.line_count() {
+        let tick = line as u64 * tpl;
+
+        if let TrackKind::Trigger = t.kind {
+            // Triggers become notes that last one line, the pulse
+            // height in A (1 to 255) becomes the velocity:
+            if let Some((_, r)) = t.row_checked(line) {
+                if r.a > 0 {
+                    let vel = if r.a > 1 { r.a / 2 } else { 1 };
+                    w.event(tick,       &[0x90 | channel, TRIGGER_NOTE, vel]);
+                    w.event(tick + tpl, &[0x80 | channel, TRIGGER_NOTE, 0]);
+                }
+            }
+        }
+
+        if let Some(r) = t.play_line(line) {
+            if let Some(note) = playing.take() {
+                w.event(tick, &[0x80 | channel, note, 0]);
+            }
+            if r.note > 127 {
+                skipped += 1;
+            } else if r.note > 1 {
+                w.event(tick, &[0x90 | channel, r.note, EXPORT_VELOCITY]);
+                playing = Some(r.note);
+            }
+        }
+
+        for col in 0..cols {
+            for i in 0..tpl {
+                let v = t.get_output_value(col, line, i as f64 / tpl as f64);
+                let v =
+                    match t.kind {
+                        TrackKind::Automation { min, max } if max > min =>
+                            (v - min) / (max - min),
+                        _ => v,
+                    };
+                let v = if v < 0.0 { 0.0 } else if v > 1.0 { 1.0 } else { v };
+
+                let (bytes, out) =
+                    match (values, col) {
+                        (ValueOut::PitchBend, 0) => {
+                            let pb = (v * 16383.0).round() as u16;
+                            ([0xE0 | channel, (pb & 0x7F) as u8, (pb >> 7) as u8], pb)
+                        },
+                        (ValueOut::PitchBend, col) => {
+                            let cc = (v * 127.0).round() as u8;
+                            ([0xB0 | channel, col as u8, cc], cc as u16)
+                        },
+                        (ValueOut::Control(base), col) => {
+                            let cc = (v * 127.0).round() as u8;
+                            ([0xB0 | channel, base + col as u8, cc], cc as u16)
+                        },
+                    };
+
+                if last_out[col] != Some(out) {
+                    w.event(tick + i, &bytes);
+                    last_out[col] = Some(out);
+                }
+            }
+        }
+    }
+
+    let end_tick = t.line_count() as u64 * tpl;
+    if let Some(note) = playing {
+        w.event(end_tick, &[0x80 | channel, note, 0]);
+    }
+    (end_tick, skipped)
+}
+
+/// Converts the tracks to a type 1 Standard MIDI File, with a tempo
+/// track first and one MIDI track per track. The tracks are played
+/// like by the Tracker, without conditions, grooves and pattern launches.
+/// A MIDI tick is a tick of the Tracker, which has `lpb` lines per beat,
+/// `tpl` ticks per line and `tick_interval` milliseconds per tick.
+/// Track number N uses the MIDI channel N modulo 16.
+/// Notes above the MIDI note range (127) are skipped.
+/// Returns the file and the number of skipped notes, or an error if
+/// the timing or the value columns can't be stored in a MIDI file.
+pub fn export_song(tracks: &[Track], lpb: usize, tpl: usize,
+                   tick_interval: usize, values: ValueOut) -> Result<(Vec<u8>, usize), String> {
+
+    let lpb      = if lpb < 1 { 1 } else { lpb };
+    let tpl      = if tpl < 1 { 1 } else { tpl };
+    let division = lpb * tpl;
+    let tempo    = division * tick_interval * 1000;
+    if division > 0x7FFF {
+        return Err(format!(
+            "{} ticks per beat are too many for MIDI (max. {})", division, 0x7FFF));
+    }
+    if tempo > 0xFFFFFF {
+        return Err(format!(
+            "a beat of {} ms is too long for MIDI", tempo / 1000));
+    }
+    let division = division as u16;
+    let tempo    = tempo as u32;
+
+    let cols =
+        tracks.iter()
+            .filter(|t| t.kind.has_values())
+            .map(|t| t.value_columns)
+            .max()
+            .unwrap_or(0);
+    let (first_cc, cc_cols) =
+        match values {
+            ValueOut::Control(base) => (base as usize, cols),
+            ValueOut::PitchBend     => (1, if cols > 0 { cols - 1 } else { 0 }),
+        };
+    if cc_cols > 0 && (first_cc < 1 || first_cc + cc_cols - 1 > MAX_EXPORT_CC) {
+        return Err(format!(
+            "the value columns need the controllers {} to {}, only 1 to {} can be used",
+            first_cc, first_cc + cc_cols - 1, MAX_EXPORT_CC));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"MThd");
+    push_u32(&mut out, 6);
+    push_u16(&mut out, 1);
+    push_u16(&mut out, (tracks.len() + 1) as u16);
+    push_u16(&mut out, division);
+
+    let mut end_tick = 0;
+    let mut skipped  = 0;
+    let mut chunks   = Vec::new();
+    for (i, t) in tracks.iter().enumerate() {
+        let mut w = TrackWriter::new();
+        let (end, s) = export_track(t, (i % 16) as u8, tpl, values, &mut w);
+        if end > end_tick { end_tick = end; }
+        skipped += s;
+        w.write_chunk(end, &mut chunks);
+    }
+
+    let mut w = TrackWriter::new();
+    w.meta(0, 0x51, &[(tempo >> 16) as u8, (tempo >> 8) as u8, tempo as u8]);
+    w.meta(0, 0x58, &[4, 2, 24, 8]);
+    w.write_chunk(end_tick, &mut out);
+
+    out.extend_from_slice(&chunks);
+    Ok((out, skipped))
+}
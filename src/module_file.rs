@@ -0,0 +1,231 @@
+use crate::track::*;
+use crate::pattern::Pattern;
+
+/// The periods of the notes C-1 to B-3 of ProTracker without finetune.
+const MOD_PERIODS : &'static [u16] = &[
+    856, 808, 762, 720, 678, 640, 604, 570, 538, 508, 480, 453,
+    428, 404, 381, 360, 340, 320, 302, 285, 269, 254, 240, 226,
+    214, 202, 190, 180, 170, 160, 151, 143, 135, 127, 120, 113,
+];
+
+/// The note number of the ProTracker note C-1 (period 856).
+const MOD_FIRST_NOTE : u8 = 48;
+
+/// XM note 1 is C-0, XM note 49 (C-4) is note number 60.
+const XM_NOTE_OFFS : u8 = 11;
+
+/// The XM note that stops the playing note.
+const XM_KEY_OFF : u8 = 97;
+
+/// Returns the note number of a MOD period, the nearest note of the
+/// period table is chosen, so that finetuned periods work too.
+fn mod_period2note(period: u16) -> u8 {
+    if period == 0 { return 0; }
+
+    let (idx, _) =
+        MOD_PERIODS.iter().enumerate()
+            .min_by_key(|(_, p)| (**p as i32 - period as i32).abs())
+            .unwrap_or((0, &0));
+    MOD_FIRST_NOTE + idx as u8
+}
+
+/// Returns the number of channels of a MOD file with 31 samples
+/// from the tag at offset 1080, or None if the tag is unknown.
+fn mod_channels(tag: &[u8]) -> Option<usize> {
+    match tag {
+        b"M.K." | b"M!K!" | b"FLT4" | b"4CHN" => Some(4),
+        b"FLT8" => Some(8),
+        _ => {
+            let s = std::str::from_utf8(tag).ok()?;
+            if s.ends_with("CHN") {
+                s[0..1].parse::<usize>().ok()
+            } else if s.ends_with("CH") {
+                s[0..2].parse::<usize>().ok()
+            } else {
+                None
+            }
+        },
+    }
+}
+
+/// Creates one Note track per channel, with the patterns and
+/// the arrangement that are filled by the importers.
+fn channel_tracks(prefix: &str, channels: usize, patterns: usize, order: &[usize]) -> Vec<Track> {
+    (0..channels).map(|ch| {
+        let mut t = Track::new_kind(&format!("{}{}", prefix, ch + 1), 64, TrackKind::Note);
+        t.patterns    = vec![Pattern::new(64); patterns];
+        t.arrangement = order.to_vec();
+        t
+    }).collect()
+}
+
+/// Converts a ProTracker MOD file with 31 samples to tracks,
+/// one Note track per channel. Each pattern of the file becomes the
+/// pattern with the same index in each track, the order list becomes
+/// the arrangement. The sample number is put into the instrument column,
+/// the effect into the A column and the effect parameter into the B column.
+pub fn import_mod(data: &[u8]) -> Result<Vec<Track>, String> {
+    if data.len() < 1084 {
+        return Err(String::from("MOD file too short"));
+    }
+
+    let channels =
+        mod_channels(&data[1080..1084])
+            .ok_or_else(|| String::from("unknown MOD format"))?;
+    if channels == 0 {
+        return Err(String::from("MOD file without channels"));
+    }
+
+    let song_len = data[950] as usize;
+    let song_len = if song_len > 128 { 128 } else { song_len };
+    let order : Vec<usize> =
+        data[952..(952 + song_len)].iter().map(|p| *p as usize).collect();
+    if order.is_empty() {
+        return Err(String::from("MOD file with empty order list"));
+    }
+
+    // All 128 order entries count, also unused patterns are stored:
+    let patterns =
+        data[952..1080].iter().map(|p| *p as usize).max().unwrap_or(0) + 1;
+
+    let pat_size = 64 * channels * 4;
+    if data.len() < 1084 + patterns * pat_size {
+        return Err(String::from("MOD file with missing patterns"));
+    }
+
+    let mut tracks = channel_tracks("mod", channels, patterns, &order);
+
+    for pat in 0..patterns {
+        let pd = &data[(1084 + pat * pat_size)..(1084 + (pat + 1) * pat_size)];
+
+        for line in 0..64 {
+            for (ch, t) in tracks.iter_mut().enumerate() {
+                let c = &pd[(line * channels + ch) * 4..];
+
+                let mut row = Row::new();
+                row.note  = mod_period2note((((c[0] & 0x0F) as u16) << 8) | c[1] as u16);
+                row.instr = (c[0] & 0xF0) | (c[2] >> 4);
+                row.a     = c[2] & 0x0F;
+                row.b     = c[3];
+                t.patterns[pat].set(line, row);
+            }
+        }
+    }
+
+    for t in tracks.iter_mut() { t.desync(); }
+    Ok(tracks)
+}
+
+fn le_u16(d: &[u8], pos: usize) -> Result<u16, String> {
+    if pos + 2 > d.len() { return Err(String::from("XM file too short")); }
+    Ok(d[pos] as u16 | ((d[pos + 1] as u16) << 8))
+}
+
+fn le_u32(d: &[u8], pos: usize) -> Result<u32, String> {
+    if pos + 4 > d.len() { return Err(String::from("XM file too short")); }
+    Ok(  d[pos] as u32 | ((d[pos + 1] as u32) << 8)
+       | ((d[pos + 2] as u32) << 16) | ((d[pos + 3] as u32) << 24))
+}
+
+/// Converts a FastTracker 2 XM file to tracks like import_mod().
+/// The patterns keep their number of rows, key offs become note offs.
+/// The volume column is not imported.
+pub fn import_xm(data: &[u8]) -> Result<Vec<Track>, String> {
+    if data.len() < 80 || &data[0..17] != b"Extended Module: " {
+        return Err(String::from("not a XM file"));
+    }
+
+    let hdr_size = le_u32(data, 60)? as usize;
+    let song_len = le_u16(data, 64)? as usize;
+    let channels = le_u16(data, 68)? as usize;
+    let patterns = le_u16(data, 70)? as usize;
+    if channels == 0 || patterns == 0 {
+        return Err(String::from("XM file without channels or patterns"));
+    }
+
+    let song_len = if song_len > 256 { 256 } else { song_len };
+    if data.len() < 80 + song_len {
+        return Err(String::from("XM file too short"));
+    }
+    let order : Vec<usize> =
+        data[80..(80 + song_len)].iter()
+            .map(|p| *p as usize)
+            .filter(|p| *p < patterns)
+            .collect();
+    if order.is_empty() {
+        return Err(String::from("XM file with empty order list"));
+    }
+
+    let mut tracks = channel_tracks("xm", channels, patterns, &order);
+
+    let mut pos = 60 + hdr_size;
+    for pat in 0..patterns {
+        let pat_hdr_len = le_u32(data, pos)? as usize;
+        let rows        = le_u16(data, pos + 5)? as usize;
+        let packed_size = le_u16(data, pos + 7)? as usize;
+        pos += pat_hdr_len;
+
+        if pos + packed_size > data.len() {
+            return Err(String::from("XM file with missing patterns"));
+        }
+        let pd = &data[pos..(pos + packed_size)];
+        pos += packed_size;
+
+        for t in tracks.iter_mut() {
+            t.patterns[pat].resize(if rows > 0 { rows } else { 64 });
+        }
+        if packed_size == 0 { continue; }
+
+        let mut p = 0;
+        let mut next = || -> Result<u8, String> {
+            let b = *pd.get(p).ok_or_else(|| String::from("bad XM pattern data"))?;
+            p += 1;
+            Ok(b)
+        };
+
+        for line in 0..rows {
+            for t in tracks.iter_mut() {
+                let b = next()?;
+                let (mut note, mut instr, mut fx, mut param) = (0, 0, 0, 0);
+                if b & 0x80 != 0 {
+                    if b & 0x01 != 0 { note  = next()?; }
+                    if b & 0x02 != 0 { instr = next()?; }
+                    if b & 0x04 != 0 { next()?; }
+                    if b & 0x08 != 0 { fx    = next()?; }
+                    if b & 0x10 != 0 { param = next()?; }
+                } else {
+                    note  = b;
+                    instr = next()?;
+                    next()?;
+                    fx    = next()?;
+                    param = next()?;
+                }
+
+                let mut row = Row::new();
+                row.note =
+                    match note {
+                        0          => 0,
+                        XM_KEY_OFF => 1,
+                        n if n < XM_KEY_OFF => n + XM_NOTE_OFFS,
+                        _          => 0,
+                    };
+                row.instr = instr;
+                row.a     = fx;
+                row.b     = param;
+                t.patterns[pat].set(line, row);
+            }
+        }
+    }
+
+    for t in tracks.iter_mut() { t.desync(); }
+    Ok(tracks)
+}
+
+/// Imports a XM or MOD file, depending on the contents.
+pub fn import_module(data: &[u8]) -> Result<Vec<Track>, String> {
+    if data.starts_with(b"Extended Module: ") {
+        import_xm(data)
+    } else {
+        import_mod(data)
+    }
+}
@@ -0,0 +1,143 @@
+use crate::track::Row;
+
+/// The header of the pattern data in the clipboard of OpenMPT/ModPlug,
+/// the format name is padded to three characters.
+const MPT_HEADER : &str = "ModPlug Tracker  XM";
+
+const NOTE_NAMES : &'static [&str] =
+    &["C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-"];
+
+/// Notes are named like in the editor, note 60 is `C-4`. OpenMPT has
+/// the octaves 0 to 9, so notes below C-0 (12) and above B-9 (131)
+/// are left out.
+fn note2mpt(note: u8) -> String {
+    match note {
+        0 => String::from("..."),
+        1 => String::from("==="),
+        n if n >= 12 && n < 132 =>
+            format!("{}{}", NOTE_NAMES[(n % 12) as usize], n / 12 - 1),
+        _ => String::from("..."),
+    }
+}
+
+fn mpt2note(s: &str) -> Result<u8, String> {
+    match s {
+        "..." | "   " => return Ok(0),
+        "===" | "^^^" | "~~~" => return Ok(1),
+        _ => (),
+    }
+
+    let name   = s.get(0..2).ok_or_else(|| format!("bad note '{}'", s))?;
+    let octave = s.get(2..3).and_then(|o| o.parse::<u8>().ok());
+    let pc     = NOTE_NAMES.iter().position(|n| *n == name);
+    match (pc, octave) {
+        (Some(pc), Some(octave)) => Ok((octave + 1) * 12 + pc as u8),
+        _ => Err(format!("bad note '{}'", s)),
+    }
+}
+
+/// Effects are written like in XM files: 0-9 and then A-Z.
+fn effect2mpt(fx: u8) -> char {
+    match fx {
+        0..=9   => (b'0' + fx) as char,
+        10..=35 => (b'A' + fx - 10) as char,
+        _       => '?',
+    }
+}
+
+fn mpt2effect(c: char) -> Option<u8> {
+    match c {
+        '0'..='9' => Some(c as u8 - b'0'),
+        'A'..='Z' => Some(c as u8 - b'A' + 10),
+        _         => None,
+    }
+}
+
+/// Converts rows to the text format of the OpenMPT/ModPlug clipboard.
+/// Each entry of `channels` holds the rows of one channel, all
+/// channels need the same number of rows.
+/// The note, instrument, A (effect) and B (effect parameter) columns
+/// are converted, the volume column stays empty.
+pub fn rows_to_mpt(channels: &[Vec<Row>]) -> String {
+    let lines = channels.iter().map(|c| c.len()).max().unwrap_or(0);
+
+    let mut s = String::from(MPT_HEADER);
+    s += "\r\n";
+
+    for line in 0..lines {
+        for c in channels.iter() {
+            let row = c.get(line).cloned().unwrap_or_else(Row::new);
+
+            s.push('|');
+            s += &note2mpt(row.note);
+            if row.instr > 0 && row.instr < 100 {
+                s += &format!("{:02}", row.instr);
+            } else {
+                s += "..";
+            }
+            s += "...";
+            if row.a == 0 && row.b == 0 {
+                s += "...";
+            } else {
+                s.push(effect2mpt(row.a));
+                s += &format!("{:02X}", row.b);
+            }
+        }
+        s += "\r\n";
+    }
+
+    s
+}
+
+/// Parses the text of the OpenMPT/ModPlug clipboard, see rows_to_mpt().
+/// Returns the rows of each channel.
+pub fn mpt_to_rows(text: &str) -> Result<Vec<Vec<Row>>, String> {
+    let mut lines = text.lines();
+    match lines.next() {
+        Some(l) if l.starts_with("ModPlug Tracker") => (),
+        _ => return Err(String::from("no OpenMPT/ModPlug pattern data")),
+    }
+
+    let mut channels : Vec<Vec<Row>> = Vec::new();
+    let mut line = 0;
+
+    for l in lines {
+        if !l.starts_with('|') { continue; }
+
+        for (ch, cell) in l[1..].split('|').enumerate() {
+            let mut row = Row::new();
+            let cell : Vec<char> = cell.chars().collect();
+            let part = |from: usize, to: usize| -> String {
+                cell.iter().skip(from).take(to - from).collect()
+            };
+
+            if cell.len() >= 3 {
+                row.note = mpt2note(&part(0, 3))?;
+            }
+            if cell.len() >= 5 {
+                row.instr = part(3, 5).parse::<u8>().unwrap_or(0);
+            }
+            // The volume column is skipped, MOD data has none:
+            let fx_pos = if cell.len() >= 11 { 8 } else { 5 };
+            if cell.len() >= fx_pos + 3 {
+                if let Some(fx) = mpt2effect(cell[fx_pos]) {
+                    row.a = fx;
+                    row.b = u8::from_str_radix(&part(fx_pos + 1, fx_pos + 3), 16)
+                                .unwrap_or(0);
+                }
+            }
+
+            while channels.len() <= ch {
+                channels.push(vec![Row::new(); line]);
+            }
+            channels[ch].push(row);
+        }
+
+        line += 1;
+        for c in channels.iter_mut() {
+            c.resize(line, Row::new());
+        }
+    }
+
+    Ok(channels)
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use serde::Deserialize;
+use crate::track::Row;
+
+/// A pattern of a track. Only the rows that are not empty are stored,
+/// sorted by their line in the pattern, so patterns with few keys
+/// are cheap to store, copy and search.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PatternSerialized", into = "PatternSerialized")]
+pub struct Pattern {
+    len:  usize,
+    rows: Vec<(usize, Row)>,
+}
+
+/// How a pattern is stored in the project file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PatternSerialized {
+    /// Older project files store every row of a pattern.
+    Dense(Vec<Row>),
+    Sparse { len: usize, rows: Vec<(usize, Row)> },
+}
+
+impl From<PatternSerialized> for Pattern {
+    fn from(ps: PatternSerialized) -> Self {
+        match ps {
+            PatternSerialized::Dense(rows) => {
+                let mut pat = Pattern::new(rows.len());
+                for (idx, row) in rows.into_iter().enumerate() {
+                    pat.set(idx, row);
+                }
+                pat
+            },
+            PatternSerialized::Sparse { len, rows } => {
+                let mut pat = Pattern::new(len);
+                for (idx, row) in rows.into_iter() {
+                    pat.set(idx, row);
+                }
+                pat
+            },
+        }
+    }
+}
+
+impl From<Pattern> for PatternSerialized {
+    fn from(pat: Pattern) -> Self {
+        PatternSerialized::Sparse { len: pat.len, rows: pat.rows }
+    }
+}
+
+impl Pattern {
+    pub fn new(len: usize) -> Self {
+        Pattern { len, rows: Vec::new() }
+    }
+
+    /// Number of lines of the pattern.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Changes the number of lines, rows beyond the new length are removed.
+    pub fn resize(&mut self, len: usize) {
+        self.len = len;
+        self.rows.retain(|(idx, _)| *idx < len);
+    }
+
+    /// Position of the row `idx` in `rows`, or where it would be inserted.
+    fn pos(&self, idx: usize) -> Result<usize, usize> {
+        self.rows.binary_search_by_key(&idx, |(i, _)| *i)
+    }
+
+    /// Returns the row at line `idx`, None if the row is empty.
+    pub fn get(&self, idx: usize) -> Option<&Row> {
+        match self.pos(idx) {
+            Ok(p)  => Some(&self.rows[p].1),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns a copy of the row at line `idx`, which may be empty.
+    pub fn row(&self, idx: usize) -> Row {
+        self.get(idx).cloned().unwrap_or_else(Row::new)
+    }
+
+    /// Returns the row at line `idx` for changing it,
+    /// an empty row is stored if there is none.
+    fn row_mut(&mut self, idx: usize) -> &mut Row {
+        let p =
+            match self.pos(idx) {
+                Ok(p)  => p,
+                Err(p) => { self.rows.insert(p, (idx, Row::new())); p },
+            };
+        &mut self.rows[p].1
+    }
+
+    /// Sets the row at line `idx`, lines beyond the
+    /// length of the pattern are ignored.
+    pub fn set(&mut self, idx: usize, mut row: Row) {
+        if idx >= self.len { return; }
+        row.trim_values();
+        if row == Row::new() {
+            self.remove(idx);
+        } else {
+            *self.row_mut(idx) = row;
+        }
+    }
+
+    /// Empties the row at line `idx`.
+    pub fn remove(&mut self, idx: usize) {
+        if let Ok(p) = self.pos(idx) {
+            self.rows.remove(p);
+        }
+    }
+
+    /// Iterates over the stored rows and their lines, in order of the lines.
+    pub fn iter(&self) -> std::slice::Iter<'_, (usize, Row)> { self.rows.iter() }
+
+    /// Changes every stored row with `f`, rows that
+    /// are empty afterwards are removed.
+    pub fn edit_rows<F>(&mut self, mut f: F) where F: FnMut(&mut Row) {
+        for (_, row) in self.rows.iter_mut() {
+            f(row);
+            row.trim_values();
+        }
+
+        let empty = Row::new();
+        self.rows.retain(|(_, row)| *row != empty);
+    }
+
+    /// Returns the first row at or after line `idx`
+    /// that has a value in the value column `col`.
+    pub fn next_with_value(&self, col: usize, idx: usize) -> Option<(usize, &Row)> {
+        let p = match self.pos(idx) { Ok(p) => p, Err(p) => p };
+        self.rows[p..].iter()
+            .find(|(_, r)| r.value_at(col).is_some())
+            .map(|(i, r)| (*i, r))
+    }
+
+    /// Returns the last row before line `idx`
+    /// that has a value in the value column `col`.
+    pub fn prev_with_value(&self, col: usize, idx: usize) -> Option<(usize, &Row)> {
+        let p = match self.pos(idx) { Ok(p) => p, Err(p) => p };
+        self.rows[..p].iter().rev()
+            .find(|(_, r)| r.value_at(col).is_some())
+            .map(|(i, r)| (*i, r))
+    }
+}
@@ -0,0 +1,354 @@
+use crate::track::*;
+use crate::pattern::Pattern;
+use serde_json::{Value, Map};
+
+/// The first line of a text project file.
+const TEXT_HEADER : &str = "# wdem-tracker text project";
+
+const NOTE_NAMES : &'static [&str] =
+    &["C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-"];
+
+/// Notes are written like `C-4`, note 60 is `C-4`,
+/// 0 is `---` and 1 (note off) is `off`.
+fn note2text(note: u8) -> String {
+    match note {
+        0 => String::from("---"),
+        1 => String::from("off"),
+        n => format!("{}{}", NOTE_NAMES[(n % 12) as usize], (n / 12) as i32 - 1),
+    }
+}
+
+fn text2note(s: &str) -> Result<u8, String> {
+    match s {
+        "---" => return Ok(0),
+        "off" => return Ok(1),
+        _ => (),
+    }
+
+    let name   = s.get(0..2).ok_or_else(|| format!("bad note '{}'", s))?;
+    let octave = s.get(2..).and_then(|o| o.parse::<i32>().ok());
+    let pc     = NOTE_NAMES.iter().position(|n| *n == name);
+    match (pc, octave) {
+        (Some(pc), Some(octave)) => {
+            let n = (octave + 1) * 12 + pc as i32;
+            if n < 2 || n > 255 { return Err(format!("bad note '{}'", s)); }
+            Ok(n as u8)
+        },
+        _ => Err(format!("bad note '{}'", s)),
+    }
+}
+
+fn int2char(int: Interpolation) -> char {
+    match int {
+        Interpolation::Empty => 'e',
+        Interpolation::Step  => '_',
+        Interpolation::Lerp  => '/',
+        Interpolation::SStep => '~',
+        Interpolation::Exp   => '^',
+    }
+}
+
+fn char2int(c: char) -> Option<Interpolation> {
+    match c {
+        'e' => Some(Interpolation::Empty),
+        '_' => Some(Interpolation::Step),
+        '/' => Some(Interpolation::Lerp),
+        '~' => Some(Interpolation::SStep),
+        '^' => Some(Interpolation::Exp),
+        _   => None,
+    }
+}
+
+/// Values are written with their interpolation, like `0.5/`.
+/// The shortest number that reads back as the same f32 is written.
+fn value2text(v: Option<(f32, Interpolation)>) -> String {
+    match v {
+        Some((v, int)) => format!("{}{}", v, int2char(int)),
+        None           => String::from("."),
+    }
+}
+
+/// Splits the interpolation character off a value like `0.5/`.
+fn split_int(s: &str) -> Result<(&str, Interpolation), String> {
+    let c   = s.chars().last().ok_or_else(|| String::from("empty value"))?;
+    let int = char2int(c).ok_or_else(|| format!("bad interpolation in '{}'", s))?;
+    Ok((&s[0..(s.len() - c.len_utf8())], int))
+}
+
+fn text2value(s: &str) -> Result<Option<(f32, Interpolation)>, String> {
+    if s == "." { return Ok(None); }
+    let (v, int) = split_int(s)?;
+    let v = v.parse::<f32>().map_err(|_| format!("bad value '{}'", s))?;
+    Ok(Some((v, int)))
+}
+
+fn cond2text(c: Condition) -> String {
+    match c {
+        Condition::Always         => String::from(""),
+        Condition::Probability(p) => format!("if:?{}", p),
+        Condition::EveryNth(n)    => format!("if:%{}", n),
+        Condition::FirstPass      => String::from("if:1st"),
+        Condition::NotFirstPass   => String::from("if:!1st"),
+    }
+}
+
+fn text2cond(s: &str) -> Result<Condition, String> {
+    let bad = || format!("bad condition '{}'", s);
+    match s {
+        "1st"  => Ok(Condition::FirstPass),
+        "!1st" => Ok(Condition::NotFirstPass),
+        s if s.starts_with('?') =>
+            Ok(Condition::Probability(s[1..].parse::<u8>().map_err(|_| bad())?)),
+        s if s.starts_with('%') =>
+            Ok(Condition::EveryNth(s[1..].parse::<u8>().map_err(|_| bad())?)),
+        _ => Err(bad()),
+    }
+}
+
+fn hex(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s, 16).map_err(|_| format!("bad hex byte '{}'", s))
+}
+
+/// Writes a row like `0004 C-4 01 00 00 0.5/`: the line in the pattern,
+/// note, instrument (hex, `..` for none), A, B and the value columns,
+/// followed by the condition and the slide if they are set.
+fn row2text(idx: usize, row: &Row) -> String {
+    let instr_s =
+        if row.instr > 0 { format!("{:02X}", row.instr) }
+        else             { String::from("..") };
+
+    let mut s = format!("{:04} {} {} {:02X} {:02X} {}",
+                        idx, note2text(row.note), instr_s, row.a, row.b,
+                        value2text(row.value));
+
+    for v in row.more_values.iter() {
+        s.push(' ');
+        s += &value2text(*v);
+    }
+    if !row.cond.is_always() {
+        s.push(' ');
+        s += &cond2text(row.cond);
+    }
+    if let Some((lines, int)) = row.slide {
+        s += &format!(" slide:{}{}", lines, int2char(int));
+    }
+
+    s
+}
+
+fn text2row(s: &str) -> Result<(usize, Row), String> {
+    let mut parts = s.split_whitespace();
+    let mut next  = |what: &str| {
+        parts.next().ok_or_else(|| format!("missing {} in row '{}'", what, s))
+    };
+
+    let idx = next("line")?.parse::<usize>().map_err(|_| format!("bad row '{}'", s))?;
+
+    let mut row = Row::new();
+    row.note  = text2note(next("note")?)?;
+    row.instr = match next("instrument")? { ".." => 0, i => hex(i)? };
+    row.a     = hex(next("A")?)?;
+    row.b     = hex(next("B")?)?;
+    row.value = text2value(next("value")?)?;
+
+    for p in parts {
+        if p.starts_with("if:") {
+            row.cond = text2cond(&p[3..])?;
+        } else if p.starts_with("slide:") {
+            let (lines, int) = split_int(&p[6..])?;
+            let lines = lines.parse::<u8>().map_err(|_| format!("bad slide '{}'", p))?;
+            row.slide = Some((lines, int));
+        } else {
+            row.more_values.push(text2value(p)?);
+        }
+    }
+
+    Ok((idx, row))
+}
+
+fn kind2text(kind: TrackKind) -> String {
+    match kind {
+        TrackKind::Mixed   => String::from("mixed"),
+        TrackKind::Note    => String::from("note"),
+        TrackKind::Trigger => String::from("trigger"),
+        TrackKind::Automation { min, max } => format!("automation {} {}", min, max),
+    }
+}
+
+fn text2kind(s: &str) -> Result<TrackKind, String> {
+    let mut parts = s.split_whitespace();
+    match parts.next() {
+        Some("mixed")   => Ok(TrackKind::Mixed),
+        Some("note")    => Ok(TrackKind::Note),
+        Some("trigger") => Ok(TrackKind::Trigger),
+        Some("automation") => {
+            let min = parts.next().and_then(|v| v.parse::<f32>().ok());
+            let max = parts.next().and_then(|v| v.parse::<f32>().ok());
+            match (min, max) {
+                (Some(min), Some(max)) => Ok(TrackKind::Automation { min, max }),
+                _ => Err(format!("bad automation range '{}'", s)),
+            }
+        },
+        _ => Err(format!("bad track kind '{}'", s)),
+    }
+}
+
+/// Writes a track, only the rows that are not empty are written.
+fn track2text(t: &TrackSerialized, out: &mut String) {
+    *out += &format!("track {}\n", t.name);
+    *out += &format!("  kind {}\n", kind2text(t.kind));
+    *out += &format!("  lpp {}\n", t.lpp);
+    *out += &format!("  columns {}\n", t.value_columns);
+    if t.mute { *out += "  mute\n"; }
+    if t.solo { *out += "  solo\n"; }
+    if let Some(g) = t.groove {
+        *out += &format!("  groove {}\n", g);
+    }
+
+    *out += "  arrangement";
+    for p in t.arrangement.iter() {
+        *out += &format!(" {}", p);
+    }
+    *out += "\n";
+
+    for (pat_idx, pat) in t.patterns.iter().enumerate() {
+        *out += &format!("  pattern {} {}\n", pat_idx, pat.len());
+        for (idx, row) in pat.iter() {
+            *out += &format!("    {}\n", row2text(*idx, row));
+        }
+    }
+}
+
+/// Converts a project to the text format. `project` is the JSON object of
+/// the project without the tracks. Each field is written to one line as
+/// `name value`, with the value in JSON. The elements of arrays are written
+/// to one line each as `name[] value`, empty arrays as `name []`. The tracks
+/// follow in a tracker like notation, with one line per row that is not empty.
+pub fn project_to_text(project: &Map<String, Value>, tracks: &[TrackSerialized]) -> String {
+    let mut out = String::from(TEXT_HEADER);
+    out += "\n";
+
+    for (key, v) in project.iter() {
+        match v {
+            Value::Array(elems) if !elems.is_empty() => {
+                for e in elems.iter() {
+                    out += &format!("{}[] {}\n", key, e);
+                }
+            },
+            v => { out += &format!("{} {}\n", key, v); },
+        }
+    }
+
+    for t in tracks.iter() {
+        track2text(t, &mut out);
+    }
+
+    out
+}
+
+/// Parses the text format, see project_to_text(). Returns the fields
+/// of the project and the tracks.
+pub fn text_to_project(text: &str) -> Result<(Map<String, Value>, Vec<TrackSerialized>), String> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, l)) if l.trim() == TEXT_HEADER => (),
+        _ => return Err(String::from("not a wdem-tracker text project")),
+    }
+
+    let mut project : Map<String, Value> = Map::new();
+    let mut tracks  : Vec<TrackSerialized> = Vec::new();
+    let mut pattern : Option<usize> = None;
+
+    for (line_nr, line) in lines {
+        let err  = |e: String| format!("line {}: {}", line_nr + 1, e);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let (key, rest) =
+            match line.find(' ') {
+                Some(i) => (&line[0..i], line[(i + 1)..].trim()),
+                None    => (line, ""),
+            };
+
+        if key == "track" {
+            tracks.push(TrackSerialized {
+                name:          rest.to_string(),
+                lpp:           0,
+                patterns:      Vec::new(),
+                arrangement:   Vec::new(),
+                kind:          TrackKind::Mixed,
+                value_columns: 1,
+                mute:          false,
+                solo:          false,
+                groove:        None,
+            });
+            pattern = None;
+            continue;
+        }
+
+        let t = match tracks.last_mut() {
+            Some(t) => t,
+            None => {
+                let (key, array) =
+                    if key.ends_with("[]") { (&key[0..(key.len() - 2)], true) }
+                    else                   { (key, false) };
+                let v : Value =
+                    serde_json::from_str(rest)
+                        .map_err(|e| err(format!("bad value of '{}': {}", key, e)))?;
+
+                if array {
+                    let entry =
+                        project.entry(key.to_string())
+                               .or_insert_with(|| Value::Array(Vec::new()));
+                    match entry {
+                        Value::Array(a) => a.push(v),
+                        _ => return Err(err(format!("'{}' is no array", key))),
+                    }
+                } else {
+                    project.insert(key.to_string(), v);
+                }
+                continue;
+            },
+        };
+
+        let num = |s: &str| {
+            s.parse::<usize>().map_err(|_| err(format!("bad number '{}'", s)))
+        };
+
+        match key {
+            "kind"    => { t.kind = text2kind(rest).map_err(err)?; },
+            "lpp"     => { t.lpp = num(rest)?; },
+            "columns" => { t.value_columns = num(rest)?; },
+            "mute"    => { t.mute = true; },
+            "solo"    => { t.solo = true; },
+            "groove"  => { t.groove = Some(num(rest)?); },
+            "arrangement" => {
+                for p in rest.split_whitespace() {
+                    t.arrangement.push(num(p)?);
+                }
+            },
+            "pattern" => {
+                let mut parts = rest.split_whitespace();
+                let idx = num(parts.next().unwrap_or(""))?;
+                let len = num(parts.next().unwrap_or(""))?;
+                if t.patterns.len() <= idx {
+                    t.patterns.resize(idx + 1, Pattern::new(0));
+                }
+                t.patterns[idx] = Pattern::new(len);
+                pattern = Some(idx);
+            },
+            _ => {
+                let pat_idx =
+                    pattern.ok_or_else(|| err(format!("row outside of a pattern")))?;
+                let (idx, row) = text2row(line).map_err(err)?;
+                let pat = &mut t.patterns[pat_idx];
+                if idx >= pat.len() {
+                    return Err(err(format!("row {} outside of the pattern", idx)));
+                }
+                pat.set(idx, row);
+            },
+        }
+    }
+
+    Ok((project, tracks))
+}
@@ -0,0 +1,141 @@
+use serde::Serialize;
+use serde::Deserialize;
+
+/// The kinds of scales, given as the semitones of their
+/// degrees above the root, see ScaleKind::intervals().
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScaleKind {
+    Major,
+    Minor,
+    HarmonicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+}
+
+const SCALE_KINDS : &'static [(ScaleKind, &str)] = &[
+    (ScaleKind::Major,           "major"),
+    (ScaleKind::Minor,           "minor"),
+    (ScaleKind::HarmonicMinor,   "harmonic"),
+    (ScaleKind::Dorian,          "dorian"),
+    (ScaleKind::Phrygian,        "phrygian"),
+    (ScaleKind::Lydian,          "lydian"),
+    (ScaleKind::Mixolydian,      "mixolydian"),
+    (ScaleKind::Locrian,         "locrian"),
+    (ScaleKind::MajorPentatonic, "pentatonic"),
+    (ScaleKind::MinorPentatonic, "minorpentatonic"),
+];
+
+const ROOT_NAMES : &'static [&str] =
+    &["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+impl ScaleKind {
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ScaleKind::Major           => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleKind::Minor           => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleKind::HarmonicMinor   => &[0, 2, 3, 5, 7, 8, 11],
+            ScaleKind::Dorian          => &[0, 2, 3, 5, 7, 9, 10],
+            ScaleKind::Phrygian        => &[0, 1, 3, 5, 7, 8, 10],
+            ScaleKind::Lydian          => &[0, 2, 4, 6, 7, 9, 11],
+            ScaleKind::Mixolydian      => &[0, 2, 4, 5, 7, 9, 10],
+            ScaleKind::Locrian         => &[0, 1, 3, 5, 6, 8, 10],
+            ScaleKind::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleKind::MinorPentatonic => &[0, 3, 5, 7, 10],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        SCALE_KINDS.iter().find(|(k, _)| k == self).map(|(_, n)| *n).unwrap_or("")
+    }
+}
+
+/// A key and scale, that constrains the note entry in the editor.
+/// Scales work on note numbers with 12 notes per octave,
+/// the note 0 and 1 are no notes (see Row::note) and are never changed.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scale {
+    /// The pitch class of the root, 0 is C, 11 is B.
+    pub root: u8,
+    pub kind: ScaleKind,
+}
+
+impl Scale {
+    pub fn new(root: u8, kind: ScaleKind) -> Self {
+        Scale { root: root % 12, kind }
+    }
+
+    /// Parses a scale like "C major", "F# minor" or "Eb dorian".
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split_whitespace();
+        let root_s    = parts.next()?;
+        let kind_s    = parts.next().unwrap_or("major").to_lowercase();
+
+        let mut chars = root_s.chars();
+        let letter    = chars.next()?.to_ascii_uppercase();
+        let mut root  =
+            ROOT_NAMES.iter().position(|n| n.starts_with(letter) && n.len() == 1)? as i32;
+        match chars.next() {
+            Some('#') => root += 1,
+            Some('b') => root -= 1,
+            None      => (),
+            _         => return None,
+        }
+
+        let kind =
+            SCALE_KINDS.iter().find(|(_, n)| *n == kind_s).map(|(k, _)| *k)?;
+        Some(Scale::new(((root + 12) % 12) as u8, kind))
+    }
+
+    pub fn name(&self) -> String {
+        format!("{} {}", ROOT_NAMES[self.root as usize], self.kind.name())
+    }
+
+    /// Returns the scale degree of the note, or None if it is
+    /// not in the scale.
+    fn degree(&self, note: u8) -> Option<usize> {
+        let pc = (note as i32 - self.root as i32 + 12 * 12) % 12;
+        self.kind.intervals().iter().position(|i| *i as i32 == pc)
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        note <= 1 || self.degree(note).is_some()
+    }
+
+    /// Returns the nearest note in the scale, a note between
+    /// two scale notes is moved down.
+    pub fn snap(&self, note: u8) -> u8 {
+        if self.contains(note) { return note; }
+
+        for d in 1..12 {
+            if note > d + 1 && self.contains(note - d) { return note - d; }
+            if note as usize + (d as usize) <= 255 && self.contains(note + d) {
+                return note + d;
+            }
+        }
+        note
+    }
+
+    /// Returns the notes of the chord built from thirds of the scale on
+    /// the `root_note`, with `size` notes: 3 for a triad, 4 for
+    /// a seventh chord. The root note is snapped to the scale first.
+    pub fn chord(&self, root_note: u8, size: usize) -> Vec<u8> {
+        let root_note = self.snap(root_note);
+        let degree    = match self.degree(root_note) { Some(d) => d, None => return vec![] };
+        let ivs       = self.kind.intervals();
+        let base      = root_note as i32 - ivs[degree] as i32;
+
+        (0..size)
+            .map(|i| {
+                let d = degree + 2 * i;
+                base + ivs[d % ivs.len()] as i32 + 12 * (d / ivs.len()) as i32
+            })
+            .filter(|n| *n > 1 && *n <= 255)
+            .map(|n| n as u8)
+            .collect()
+    }
+}
@@ -0,0 +1,237 @@
+use crate::track::*;
+use crate::song_player::*;
+use serde_json::Value;
+
+/// The names of the op input types (see wctr_signal_ops::signals::OpIn)
+/// in the order of their type numbers in the song blob.
+const OP_IN_TYPES : &'static [&str] = &[
+    "Constant", "Reg", "RegMix2", "RegAdd", "RegMul",
+    "RegAddMul", "RegMulAdd", "RegLerp", "RegSStep", "RegMap",
+];
+
+fn push_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn push_f32(out: &mut Vec<u8>, v: f32) {
+    let bits = v.to_bits();
+    for i in 0..4 {
+        out.push((bits >> (i * 8)) as u8);
+    }
+}
+
+fn push_str(out: &mut Vec<u8>, s: &str) {
+    push_varint(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn int2byte(int: Interpolation) -> u8 {
+    match int {
+        Interpolation::Empty => INT_EMPTY,
+        Interpolation::Step  => INT_STEP,
+        Interpolation::Lerp  => INT_LERP,
+        Interpolation::SStep => INT_SSTEP,
+        Interpolation::Exp   => INT_EXP,
+    }
+}
+
+/// Writes the rows of a pattern that are not empty. The line of
+/// each row is written as distance to the line of the previous row.
+fn write_pattern(out: &mut Vec<u8>, t: &Track, pat_idx: usize) {
+    let pat = &t.patterns[pat_idx];
+
+    let mut rows = Vec::new();
+    let mut count = 0;
+    let mut next_line = 0;
+
+    for (line, row) in pat.iter() {
+        if *line >= pat.len() { continue; }
+
+        let mut values = 0;
+        for col in 0..t.value_columns {
+            if row.value_at(col).is_some() { values |= 1 << col; }
+        }
+
+        let mut flags = 0;
+        if row.note  > 0 { flags |= ROW_NOTE; }
+        if row.instr > 0 { flags |= ROW_INSTR; }
+        if row.a     > 0 { flags |= ROW_A; }
+        if row.b     > 0 { flags |= ROW_B; }
+        if row.slide.is_some() { flags |= ROW_SLIDE; }
+        if values    > 0 { flags |= ROW_VALUES; }
+        if flags == 0 { continue; }
+
+        push_varint(&mut rows, *line - next_line);
+        next_line = *line + 1;
+        count += 1;
+
+        rows.push(flags);
+        if row.note  > 0 { rows.push(row.note); }
+        if row.instr > 0 { rows.push(row.instr); }
+        if row.a     > 0 { rows.push(row.a); }
+        if row.b     > 0 { rows.push(row.b); }
+        if let Some((lines, int)) = row.slide {
+            rows.push(lines);
+            rows.push(int2byte(int));
+        }
+        if values > 0 {
+            push_varint(&mut rows, values);
+            for col in 0..t.value_columns {
+                if let Some((v, int)) = row.value_at(col) {
+                    rows.push(int2byte(int));
+                    push_f32(&mut rows, v);
+                }
+            }
+        }
+    }
+
+    let mut pd = Vec::new();
+    push_varint(&mut pd, pat.len());
+    push_varint(&mut pd, count);
+    pd.extend_from_slice(&rows);
+
+    // The size allows the player to skip the pattern:
+    push_varint(out, pd.len());
+    out.extend_from_slice(&pd);
+}
+
+/// Writes a track, only the patterns used by the arrangement
+/// are written and they are numbered in the order of their first use.
+fn write_track(out: &mut Vec<u8>, t: &Track) {
+    match t.kind {
+        TrackKind::Mixed   => out.push(KIND_MIXED),
+        TrackKind::Note    => out.push(KIND_NOTE),
+        TrackKind::Trigger => out.push(KIND_TRIGGER),
+        TrackKind::Automation { min, max } => {
+            out.push(KIND_AUTOMATION);
+            push_f32(out, min);
+            push_f32(out, max);
+        },
+    }
+
+    let mut flags = 0;
+    if t.mute { flags |= TRACK_MUTE; }
+    if t.solo { flags |= TRACK_SOLO; }
+    out.push(flags);
+    push_varint(out, t.value_columns);
+
+    let mut used : Vec<usize> = Vec::new();
+    let arrangement : Vec<usize> =
+        t.arrangement.iter().filter(|p| **p < t.patterns.len()).map(|p| {
+            match used.iter().position(|u| u == p) {
+                Some(i) => i,
+                None    => { used.push(*p); used.len() - 1 },
+            }
+        }).collect();
+
+    push_varint(out, arrangement.len());
+    for p in arrangement.iter() {
+        push_varint(out, *p);
+    }
+
+    push_varint(out, used.len());
+    for p in used.iter() {
+        write_pattern(out, t, *p);
+    }
+}
+
+/// Writes the op input values, as returned by
+/// OperatorInputSettings::save_input_values() and converted to JSON.
+fn write_op_inputs(out: &mut Vec<u8>, op_inputs: &Value) -> Result<(), String> {
+    let bad = || String::from("bad op input values");
+    let ops = op_inputs.as_array().ok_or_else(bad)?;
+
+    push_varint(out, ops.len());
+    for op in ops.iter() {
+        let op_name = op.get(0).and_then(|n| n.as_str()).ok_or_else(bad)?;
+        let inputs  = op.get(1).and_then(|i| i.as_array()).ok_or_else(bad)?;
+
+        push_str(out, op_name);
+        push_varint(out, inputs.len());
+        for inp in inputs.iter() {
+            let in_name = inp.get(0).and_then(|n| n.as_str()).ok_or_else(bad)?;
+            let opin    = inp.get(1).and_then(|o| o.as_object()).ok_or_else(bad)?;
+            let (typ, args) = opin.iter().next().ok_or_else(bad)?;
+
+            let typ_idx =
+                OP_IN_TYPES.iter().position(|t| t == typ)
+                    .ok_or_else(|| format!("unknown op input type '{}'", typ))?;
+
+            let args : Vec<f32> =
+                match args {
+                    Value::Array(a) => a.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect(),
+                    v               => vec![v.as_f64().unwrap_or(0.0) as f32],
+                };
+
+            push_str(out, in_name);
+            out.push(typ_idx as u8);
+            push_varint(out, args.len());
+            for a in args.iter() {
+                push_f32(out, *a);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the song to a compact binary blob for size limited intros,
+/// that is played by SongPlayer. It contains the timing, the tracks with
+/// their arrangement and the patterns used by it, and the op input values.
+/// `groove` is the groove of the whole song (see Tracker::groove).
+/// `op_inputs` are the op input values in their JSON form as stored in
+/// the project file.
+///
+/// Numbers are written as variable length integers, with 7 bits per byte
+/// and the highest bit set if another byte follows. Only the rows that
+/// are not empty are written, each as distance to the previous row, a
+/// byte with flags for the columns that are set and the set columns.
+///
+/// The player does not know grooves and row conditions, so songs that
+/// use them are rejected instead of playing differently than in the
+/// Tracker. The instrument table is not written, the player passes the
+/// instrument numbers of the notes on to the demo.
+pub fn export_song_blob(tracks: &[Track], lpb: usize, tpl: usize, tick_interval: usize,
+                        groove: Option<usize>, op_inputs: &Value) -> Result<Vec<u8>, String> {
+
+    if tracks.len() > MAX_TRACKS {
+        return Err(format!("the player supports at most {} tracks", MAX_TRACKS));
+    }
+    if let Some(t) = tracks.iter().find(|t| t.value_columns > MAX_VALUE_COLUMNS) {
+        return Err(format!("track '{}': the player supports at most {} value columns",
+                           t.name, MAX_VALUE_COLUMNS));
+    }
+    if groove.is_some() {
+        return Err(String::from("the player does not support the song groove"));
+    }
+    if let Some(t) = tracks.iter().find(|t| t.groove.is_some()) {
+        return Err(format!("track '{}': the player does not support grooves", t.name));
+    }
+    let has_cond =
+        |t: &Track| t.patterns.iter().any(|p| p.iter().any(|(_, r)| !r.cond.is_always()));
+    if let Some(t) = tracks.iter().find(|t| has_cond(t)) {
+        return Err(format!("track '{}': the player does not support row conditions", t.name));
+    }
+
+    let song_lines = tracks.iter().map(|t| t.line_count()).max().unwrap_or(0);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(BLOB_MAGIC);
+    push_varint(&mut out, lpb);
+    push_varint(&mut out, tpl);
+    push_varint(&mut out, tick_interval);
+    push_varint(&mut out, song_lines);
+
+    push_varint(&mut out, tracks.len());
+    for t in tracks.iter() {
+        write_track(&mut out, t);
+    }
+
+    write_op_inputs(&mut out, op_inputs)?;
+
+    Ok(out)
+}
@@ -0,0 +1,641 @@
+// This module only depends on `core`, so that it can be copied into
+// a `#![no_std]` intro without allocator. The song blob is written
+// by song_blob::export_song_blob().
+
+/// The first bytes of a song blob, the last byte is the version.
+pub const BLOB_MAGIC : &[u8] = b"WDT1";
+
+/// Maximum number of tracks the player can play.
+pub const MAX_TRACKS        : usize = 64;
+/// Maximum number of value columns of a track the player can play.
+pub const MAX_VALUE_COLUMNS : usize = 8;
+
+pub const KIND_MIXED      : u8 = 0;
+pub const KIND_NOTE       : u8 = 1;
+pub const KIND_TRIGGER    : u8 = 2;
+pub const KIND_AUTOMATION : u8 = 3;
+
+pub const TRACK_MUTE : u8 = 0x01;
+pub const TRACK_SOLO : u8 = 0x02;
+
+pub const ROW_NOTE   : u8 = 0x01;
+pub const ROW_INSTR  : u8 = 0x02;
+pub const ROW_A      : u8 = 0x04;
+pub const ROW_B      : u8 = 0x08;
+pub const ROW_SLIDE  : u8 = 0x10;
+pub const ROW_VALUES : u8 = 0x20;
+
+pub const INT_EMPTY : u8 = 0;
+pub const INT_STEP  : u8 = 1;
+pub const INT_LERP  : u8 = 2;
+pub const INT_SSTEP : u8 = 3;
+pub const INT_EXP   : u8 = 4;
+
+/// The most arguments an op input type has (RegMap).
+const MAX_OP_IN_ARGS : usize = 5;
+
+#[derive(Debug, Clone, Copy)]
+struct Reader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self { Reader { data, pos } }
+
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// Returns None if the number does not fit into an usize,
+    /// which has only 32 bits on many targets of intros.
+    fn varint(&mut self) -> Option<usize> {
+        let bits      = usize::BITS as usize;
+        let mut v     = 0;
+        let mut shift = 0;
+        loop {
+            let b   = self.u8()?;
+            let low = (b & 0x7F) as usize;
+            if shift > 0 && (low >> (bits - shift)) != 0 { return None; }
+            v |= low << shift;
+            if b & 0x80 == 0 { return Some(v); }
+            shift += 7;
+            if shift >= bits { return None; }
+        }
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        let mut bits = 0;
+        for i in 0..4 {
+            bits |= (self.u8()? as u32) << (i * 8);
+        }
+        Some(f32::from_bits(bits))
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.varint()?;
+        let end = self.pos.checked_add(len)?;
+        let s   = self.data.get(self.pos..end)?;
+        self.pos = end;
+        core::str::from_utf8(s).ok()
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.pos = self.pos.checked_add(len)?;
+        Some(())
+    }
+}
+
+/// Interpolates like the InterpolationState of a Track.
+#[derive(Debug, Clone, Copy)]
+struct Interpol {
+    line_a: usize,
+    line_b: usize,
+    val_a:  f32,
+    val_b:  f32,
+    int:    u8,
+}
+
+impl Interpol {
+    fn new() -> Self {
+        Interpol { line_a: 0, line_b: 0, val_a: 0.0, val_b: 0.0, int: INT_EMPTY }
+    }
+
+    fn set(&mut self, line_a: usize, a: Option<(f32, u8)>, line_b: usize, val_b: f32) {
+        let (val_a, int) = a.unwrap_or((0.0, INT_STEP));
+        self.line_a = line_a;
+        self.val_a  = val_a;
+        self.int    = int;
+        self.line_b = line_b;
+        self.val_b  = val_b;
+    }
+
+    fn value(&self, line: usize, fract_next_line: f64) -> f32 {
+        if line < self.line_a { return 0.0; }
+
+        let diff   = if self.line_b > self.line_a { self.line_b - self.line_a } else { 1 };
+        let x      = (line as f64 + fract_next_line - self.line_a as f64) / diff as f64;
+        let (a, b) = (self.val_a as f64, self.val_b as f64);
+
+        match self.int {
+            INT_STEP => if line == self.line_b { self.val_b } else { self.val_a },
+            INT_LERP => (a * (1.0 - x) + b * x) as f32,
+            INT_SSTEP => {
+                let x = if x < 0.0 { 0.0 } else if x > 1.0 { 1.0 } else { x };
+                let x = x * x * (3.0 - 2.0 * x);
+                (a * (1.0 - x) + b * x) as f32
+            },
+            INT_EXP => {
+                let x = x * x;
+                (a * (1.0 - x) + b * x) as f32
+            },
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BlobRow {
+    line:   usize,
+    note:   u8,
+    instr:  u8,
+    a:      u8,
+    b:      u8,
+    slide:  Option<(u8, u8)>,
+    values: [Option<(f32, u8)>; MAX_VALUE_COLUMNS],
+}
+
+impl BlobRow {
+    fn read(r: &mut Reader, line: usize) -> Option<Self> {
+        let flags = r.u8()?;
+        let mut row = BlobRow {
+            line,
+            note:   if flags & ROW_NOTE  != 0 { r.u8()? } else { 0 },
+            instr:  if flags & ROW_INSTR != 0 { r.u8()? } else { 0 },
+            a:      if flags & ROW_A     != 0 { r.u8()? } else { 0 },
+            b:      if flags & ROW_B     != 0 { r.u8()? } else { 0 },
+            slide:  None,
+            values: [None; MAX_VALUE_COLUMNS],
+        };
+        if flags & ROW_SLIDE != 0 {
+            row.slide = Some((r.u8()?, r.u8()?));
+        }
+        if flags & ROW_VALUES != 0 {
+            let mut mask = r.varint()?;
+            let mut col  = 0;
+            while mask > 0 {
+                if mask & 1 != 0 {
+                    let int = r.u8()?;
+                    let v   = r.f32()?;
+                    if col < MAX_VALUE_COLUMNS { row.values[col] = Some((v, int)); }
+                }
+                mask >>= 1;
+                col   += 1;
+            }
+        }
+        Some(row)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PlayerTrack {
+    kind:          u8,
+    min:           f32,
+    max:           f32,
+    flags:         u8,
+    value_columns: usize,
+    /// Offset of the arrangement in the blob.
+    arr_offs:      usize,
+    /// Offset of the patterns in the blob.
+    pat_offs:      usize,
+    line_count:    usize,
+    played_line:   Option<usize>,
+    silenced:      bool,
+    trigger:       f32,
+    pitch:         Interpol,
+    interpol:      [Interpol; MAX_VALUE_COLUMNS],
+}
+
+impl PlayerTrack {
+    fn new() -> Self {
+        PlayerTrack {
+            kind:          KIND_MIXED,
+            min:           0.0,
+            max:           0.0,
+            flags:         0,
+            value_columns: 0,
+            arr_offs:      0,
+            pat_offs:      0,
+            line_count:    0,
+            played_line:   None,
+            silenced:      false,
+            trigger:       0.0,
+            pitch:         Interpol::new(),
+            interpol:      [Interpol::new(); MAX_VALUE_COLUMNS],
+        }
+    }
+
+    fn has_notes(&self) -> bool {
+        self.kind == KIND_MIXED || self.kind == KIND_NOTE
+    }
+
+    fn has_values(&self) -> bool {
+        self.kind == KIND_MIXED || self.kind == KIND_AUTOMATION
+    }
+
+    fn output_count(&self) -> usize {
+        if self.has_values() { self.value_columns } else { 1 }
+    }
+
+    fn pitch(&self, line: usize, fract_next_line: f64) -> Option<f32> {
+        let p = &self.pitch;
+        if p.int == INT_EMPTY { return None; }
+
+        if line < p.line_a || line >= p.line_b {
+            Some(p.val_b)
+        } else {
+            Some(p.value(line, fract_next_line))
+        }
+    }
+
+    fn output_value(&self, out_idx: usize, line: usize, fract_next_line: f64) -> f32 {
+        match self.kind {
+            KIND_NOTE    => self.pitch(line, fract_next_line).unwrap_or(0.0),
+            KIND_TRIGGER => self.trigger,
+            _ => {
+                let v =
+                    match self.interpol.get(out_idx) {
+                        Some(i) => i.value(line, fract_next_line),
+                        None    => 0.0,
+                    };
+                if self.kind != KIND_AUTOMATION { return v; }
+                if v < self.min      { self.min }
+                else if v > self.max { self.max }
+                else                 { v }
+            },
+        }
+    }
+}
+
+/// A note that the player starts, see SongPlayer::tick().
+/// Note 1 stops the playing note of the track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent {
+    pub note:  u8,
+    pub instr: u8,
+    pub a:     u8,
+    pub b:     u8,
+}
+
+/// An op input value of the song blob, like the
+/// OpIn of the wctr_signal_ops crate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpInValue {
+    Constant(f32),
+    Reg(usize),
+    RegMix2(usize, usize, f32),
+    RegAdd(usize, f32),
+    RegMul(usize, f32),
+    RegAddMul(usize, f32, f32),
+    RegMulAdd(usize, f32, f32),
+    RegLerp(usize, f32, f32),
+    RegSStep(usize, f32, f32),
+    RegMap(usize, f32, f32, f32, f32),
+}
+
+impl OpInValue {
+    fn from_args(typ: u8, a: &[f32; MAX_OP_IN_ARGS]) -> Option<Self> {
+        let r = |i: usize| a[i] as usize;
+        Some(match typ {
+            0 => OpInValue::Constant(a[0]),
+            1 => OpInValue::Reg(r(0)),
+            2 => OpInValue::RegMix2(r(0), r(1), a[2]),
+            3 => OpInValue::RegAdd(r(0), a[1]),
+            4 => OpInValue::RegMul(r(0), a[1]),
+            5 => OpInValue::RegAddMul(r(0), a[1], a[2]),
+            6 => OpInValue::RegMulAdd(r(0), a[1], a[2]),
+            7 => OpInValue::RegLerp(r(0), a[1], a[2]),
+            8 => OpInValue::RegSStep(r(0), a[1], a[2]),
+            9 => OpInValue::RegMap(r(0), a[1], a[2], a[3], a[4]),
+            _ => return None,
+        })
+    }
+}
+
+/// Iterates over the op input values of a song blob,
+/// see SongPlayer::op_inputs().
+#[derive(Debug, Clone, Copy)]
+pub struct OpInputs<'a> {
+    r:           Reader<'a>,
+    ops_left:    usize,
+    inputs_left: usize,
+    op_name:     &'a str,
+}
+
+impl<'a> Iterator for OpInputs<'a> {
+    /// The name of the op, the name of the input and the value.
+    type Item = (&'a str, &'a str, OpInValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.inputs_left == 0 {
+            if self.ops_left == 0 { return None; }
+            self.ops_left   -= 1;
+            self.op_name     = self.r.str()?;
+            self.inputs_left = self.r.varint()?;
+        }
+        self.inputs_left -= 1;
+
+        let in_name = self.r.str()?;
+        let typ     = self.r.u8()?;
+        let count   = self.r.varint()?;
+        let mut args = [0.0; MAX_OP_IN_ARGS];
+        for i in 0..count {
+            let a = self.r.f32()?;
+            if i < MAX_OP_IN_ARGS { args[i] = a; }
+        }
+
+        Some((self.op_name, in_name, OpInValue::from_args(typ, &args)?))
+    }
+}
+
+/// Plays a song blob that was written by song_blob::export_song_blob().
+/// It plays like the Tracker: call tick() every tick interval, it
+/// reports the started notes, and read the output values of the tracks
+/// with output_values(). The blob is not copied or decoded up front,
+/// the rows are read from the blob when a new line is played.
+pub struct SongPlayer<'a> {
+    data:          &'a [u8],
+    lpb:           usize,
+    tpl:           usize,
+    tick_interval: usize,
+    song_lines:    usize,
+    tick_count:    usize,
+    play_line:     i32,
+    track_count:   usize,
+    tracks:        [PlayerTrack; MAX_TRACKS],
+    op_offs:       usize,
+}
+
+impl<'a> SongPlayer<'a> {
+    /// Reads the header and the track layout of the blob.
+    /// Returns None if the blob is broken.
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(BLOB_MAGIC) { return None; }
+
+        let mut r = Reader::new(data, BLOB_MAGIC.len());
+        let mut p = SongPlayer {
+            data,
+            lpb:           r.varint()?,
+            tpl:           r.varint()?,
+            tick_interval: r.varint()?,
+            song_lines:    r.varint()?,
+            tick_count:    0,
+            play_line:     -1,
+            track_count:   r.varint()?,
+            tracks:        [PlayerTrack::new(); MAX_TRACKS],
+            op_offs:       0,
+        };
+        if p.track_count > MAX_TRACKS || p.tpl == 0 { return None; }
+
+        for t in p.tracks.iter_mut().take(p.track_count) {
+            t.kind = r.u8()?;
+            if t.kind == KIND_AUTOMATION {
+                t.min = r.f32()?;
+                t.max = r.f32()?;
+            }
+            t.flags         = r.u8()?;
+            t.value_columns = r.varint()?;
+            if t.value_columns > MAX_VALUE_COLUMNS { return None; }
+
+            t.arr_offs = r.pos;
+            let arr_len = r.varint()?;
+            for _ in 0..arr_len { r.varint()?; }
+
+            t.pat_offs = r.pos;
+            let pat_count = r.varint()?;
+            for _ in 0..pat_count {
+                let size = r.varint()?;
+                r.skip(size)?;
+            }
+        }
+        p.op_offs = r.pos;
+
+        for i in 0..p.track_count {
+            let mut count : usize = 0;
+            let mut arr   = Reader::new(data, p.tracks[i].arr_offs);
+            for _ in 0..arr.varint()? {
+                count = count.checked_add(p.pattern(i, arr.varint()?)?.0)?;
+            }
+            p.tracks[i].line_count = count;
+        }
+
+        Some(p)
+    }
+
+    pub fn lpb(&self) -> usize { self.lpb }
+    pub fn tpl(&self) -> usize { self.tpl }
+
+    /// Milliseconds per tick.
+    pub fn tick_interval(&self) -> usize { self.tick_interval }
+
+    /// Number of lines of the song, after the last line
+    /// the song starts again.
+    pub fn song_lines(&self) -> usize { self.song_lines }
+
+    pub fn track_count(&self) -> usize { self.track_count }
+
+    /// The line that plays, -1 before the first tick().
+    pub fn play_line(&self) -> i32 { self.play_line }
+
+    pub fn song_pos_in_s(&self) -> f32 {
+        ((self.tick_count as f64 * self.tick_interval as f64) / 1000.0) as f32
+    }
+
+    /// Returns the length of the pattern `pat_idx` of a track
+    /// and a reader at its first row.
+    fn pattern(&self, track_idx: usize, pat_idx: usize) -> Option<(usize, usize, Reader<'a>)> {
+        let mut r = Reader::new(self.data, self.tracks[track_idx].pat_offs);
+        if pat_idx >= r.varint()? { return None; }
+        for _ in 0..pat_idx {
+            let size = r.varint()?;
+            r.skip(size)?;
+        }
+        r.varint()?;
+        let len   = r.varint()?;
+        let count = r.varint()?;
+        Some((len, count, r))
+    }
+
+    /// Calls `f` with the rows of the track in the order of their lines,
+    /// until `f` returns false.
+    fn rows<F>(&self, track_idx: usize, mut f: F) -> Option<()>
+        where F: FnMut(&BlobRow) -> bool {
+
+        let mut arr   = Reader::new(self.data, self.tracks[track_idx].arr_offs);
+        let mut start : usize = 0;
+        for _ in 0..arr.varint()? {
+            let (len, count, mut r) = self.pattern(track_idx, arr.varint()?)?;
+            let mut line : usize = 0;
+            for _ in 0..count {
+                line = line.checked_add(r.varint()?)?;
+                if !f(&BlobRow::read(&mut r, start.checked_add(line)?)?) { return Some(()); }
+                line += 1;
+            }
+            start = start.checked_add(len)?;
+        }
+        Some(())
+    }
+
+    /// Plays `line` of a track, like Track::play_line(). Sets up the
+    /// interpolations and returns the note that starts.
+    fn play_track_line(&mut self, track_idx: usize, line: usize) -> Option<NoteEvent> {
+        let cols = self.tracks[track_idx].value_columns;
+
+        let mut prev  : [Option<BlobRow>; MAX_VALUE_COLUMNS] = [None; MAX_VALUE_COLUMNS];
+        let mut next  : [Option<BlobRow>; MAX_VALUE_COLUMNS] = [None; MAX_VALUE_COLUMNS];
+        let mut row   = None;
+        let mut instr = 0;
+
+        self.rows(track_idx, |r| {
+            if r.line <= line && r.instr > 0 { instr = r.instr; }
+            if r.line == line { row = Some(*r); }
+
+            // Done after the line, once the next value of every column is found:
+            let mut done = r.line >= line;
+            for col in 0..cols {
+                if r.values[col].is_some() {
+                    if r.line < line             { prev[col] = Some(*r); }
+                    else if next[col].is_none()  { next[col] = Some(*r); }
+                }
+                if next[col].is_none() { done = false; }
+            }
+            !done
+        });
+
+        let t        = &mut self.tracks[track_idx];
+        let end_line = if t.line_count > 0 { t.line_count - 1 } else { 0 };
+
+        for col in 0..cols {
+            let i = &mut t.interpol[col];
+            match (prev[col], next[col]) {
+                (Some(p), Some(n)) => i.set(p.line, p.values[col], n.line,
+                                            n.values[col].map(|v| v.0).unwrap_or(0.0)),
+                (None, Some(n))    => i.set(n.line, n.values[col], end_line, 0.0),
+                (Some(p), None)    => i.set(p.line, p.values[col], end_line, 0.0),
+                (None, None)       => i.int = INT_EMPTY,
+            }
+        }
+
+        t.trigger = row.map(|r| r.a as f32 / 255.0).unwrap_or(0.0);
+
+        if !t.has_notes() { return None; }
+        let r =
+            match row {
+                Some(r) if r.note > 0 => r,
+                _ => return None,
+            };
+
+        if r.note == 1 {
+            t.pitch.int = INT_EMPTY;
+            return Some(NoteEvent { note: 1, instr: r.instr, a: r.a, b: r.b });
+        }
+
+        let note = r.note as f32;
+        match (r.slide, t.pitch(line, 0.0)) {
+            (Some((lines, int)), Some(from)) => {
+                t.pitch.set(line, Some((from, int)), line + lines as usize, note);
+                None
+            },
+            _ => {
+                t.pitch.set(line, Some((note, INT_STEP)), line, note);
+                Some(NoteEvent { note: r.note, instr, a: r.a, b: r.b })
+            },
+        }
+    }
+
+    /// Starts the song from the beginning.
+    pub fn restart(&mut self) {
+        self.tick_count = 0;
+        self.play_line  = -1;
+        for t in self.tracks.iter_mut() {
+            t.played_line = None;
+            t.pitch.int   = INT_EMPTY;
+        }
+    }
+
+    /// Advances the song by one tick. When a new line starts,
+    /// `emit` is called with the index of the track and the note
+    /// for each note that starts. Muted tracks get a note off
+    /// once, so that a still playing note stops.
+    pub fn tick<F>(&mut self, mut emit: F) where F: FnMut(usize, &NoteEvent) {
+        if self.song_lines == 0 { return; }
+
+        self.tick_count += 1;
+        let mut line = self.tick_count / self.tpl;
+        if line >= self.song_lines {
+            self.restart();
+            self.tick_count = 1;
+            line = 0;
+        }
+        self.play_line = line as i32;
+
+        let any_solo =
+            self.tracks.iter().take(self.track_count)
+                .any(|t| t.flags & TRACK_SOLO != 0);
+
+        for track_idx in 0..self.track_count {
+            if self.tracks[track_idx].played_line == Some(line) { continue; }
+            self.tracks[track_idx].played_line = Some(line);
+
+            let ev = self.play_track_line(track_idx, line);
+
+            let t       = &mut self.tracks[track_idx];
+            let audible = t.flags & TRACK_MUTE == 0 && (t.flags & TRACK_SOLO != 0 || !any_solo);
+            if !audible {
+                if !t.silenced {
+                    t.silenced = true;
+                    emit(track_idx, &NoteEvent { note: 1, instr: 0, a: 0, b: 0 });
+                }
+                continue;
+            }
+            t.silenced = false;
+
+            if let Some(ev) = ev {
+                emit(track_idx, &ev);
+            }
+        }
+    }
+
+    /// Returns the pitch of the playing note of a track as note
+    /// number, which changes during a slide. None if no note plays.
+    pub fn pitch(&self, track_idx: usize) -> Option<f32> {
+        let (line, fract) = self.line_pos()?;
+        self.tracks.get(track_idx)?.pitch(line, fract)
+    }
+
+    fn line_pos(&self) -> Option<(usize, f64)> {
+        if self.play_line < 0 { return None; }
+        Some((self.play_line as usize,
+              (self.tick_count % self.tpl) as f64 / self.tpl as f64))
+    }
+
+    /// Number of output values of all tracks, like the
+    /// Track::output_count() of each track added up.
+    pub fn output_count(&self) -> usize {
+        self.tracks.iter().take(self.track_count).map(|t| t.output_count()).sum()
+    }
+
+    /// Writes the output values of the tracks to `out`, in the
+    /// same order as the Tracker does. Trigger tracks that are
+    /// not audible output 0.0.
+    pub fn output_values(&self, out: &mut [f32]) {
+        let (line, fract) = self.line_pos().unwrap_or((0, 0.0));
+
+        let any_solo =
+            self.tracks.iter().take(self.track_count)
+                .any(|t| t.flags & TRACK_SOLO != 0);
+
+        let mut out_idx = 0;
+        for t in self.tracks.iter().take(self.track_count) {
+            let audible = t.flags & TRACK_MUTE == 0 && (t.flags & TRACK_SOLO != 0 || !any_solo);
+            for col in 0..t.output_count() {
+                if out_idx >= out.len() { return; }
+                out[out_idx] =
+                    if !audible && t.kind == KIND_TRIGGER { 0.0 }
+                    else { t.output_value(col, line, fract) };
+                out_idx += 1;
+            }
+        }
+
+        for v in out[out_idx..].iter_mut() { *v = 0.0; }
+    }
+
+    /// Iterates over the op input values that were stored with the song.
+    pub fn op_inputs(&self) -> OpInputs<'a> {
+        let mut r = Reader::new(self.data, self.op_offs);
+        let ops_left = r.varint().unwrap_or(0);
+        OpInputs { r, ops_left, inputs_left: 0, op_name: "" }
+    }
+}
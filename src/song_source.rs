@@ -0,0 +1,365 @@
+use crate::track::*;
+use crate::pattern::Pattern;
+use crate::tracker::*;
+use crate::instrument::Instrument;
+use crate::groove::Groove;
+use serde_json::Value;
+use wctr_signal_ops::Simulator;
+use wctr_signal_ops::signals::OpIn;
+
+/// A row of a pattern in generated source, see song_to_rust().
+/// `values` holds the value columns, starting with the first one.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstRow {
+    pub line:   usize,
+    pub note:   u8,
+    pub instr:  u8,
+    pub a:      u8,
+    pub b:      u8,
+    pub values: &'static [Option<(f32, Interpolation)>],
+    pub cond:   Condition,
+    pub slide:  Option<(u8, Interpolation)>,
+}
+
+impl ConstRow {
+    pub fn to_row(&self) -> Row {
+        let mut row = Row::new();
+        row.note  = self.note;
+        row.instr = self.instr;
+        row.a     = self.a;
+        row.b     = self.b;
+        row.cond  = self.cond;
+        row.slide = self.slide;
+        for (col, v) in self.values.iter().enumerate() {
+            row.set_value_at(col, *v);
+        }
+        row
+    }
+}
+
+/// A pattern in generated source, only the rows
+/// that are not empty are stored.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstPattern {
+    pub len:  usize,
+    pub rows: &'static [ConstRow],
+}
+
+impl ConstPattern {
+    pub fn to_pattern(&self) -> Pattern {
+        let mut pat = Pattern::new(self.len);
+        for r in self.rows.iter() {
+            pat.set(r.line, r.to_row());
+        }
+        pat
+    }
+}
+
+/// A track in generated source, like TrackSerialized.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstTrack {
+    pub name:          &'static str,
+    pub kind:          TrackKind,
+    pub lpp:           usize,
+    pub value_columns: usize,
+    pub mute:          bool,
+    pub solo:          bool,
+    pub groove:        Option<usize>,
+    pub patterns:      &'static [ConstPattern],
+    pub arrangement:   &'static [usize],
+}
+
+impl ConstTrack {
+    pub fn to_track(&self) -> Track {
+        let mut t = Track::new_kind(self.name, self.lpp, self.kind);
+        t.deserialize_contents(&TrackSerialized {
+            name:          self.name.to_string(),
+            lpp:           self.lpp,
+            patterns:      self.patterns.iter().map(|p| p.to_pattern()).collect(),
+            arrangement:   self.arrangement.to_vec(),
+            kind:          self.kind,
+            value_columns: self.value_columns,
+            mute:          self.mute,
+            solo:          self.solo,
+            groove:        self.groove,
+        });
+        t
+    }
+}
+
+/// An instrument in generated source, see Instrument.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstInstrument {
+    pub name:       &'static str,
+    pub groups:     &'static [usize],
+    pub params:     &'static [(&'static str, &'static str, f32)],
+    pub freq_input: Option<(&'static str, &'static str)>,
+}
+
+impl ConstInstrument {
+    pub fn to_instrument(&self) -> Instrument {
+        let mut instr = Instrument::new(self.name);
+        instr.groups = self.groups.to_vec();
+        instr.params =
+            self.params.iter()
+                .map(|(op, inp, v)| (op.to_string(), inp.to_string(), *v))
+                .collect();
+        instr.freq_input =
+            self.freq_input.map(|(op, inp)| (op.to_string(), inp.to_string()));
+        instr
+    }
+}
+
+/// A song in generated source, see song_to_rust(). It rebuilds the
+/// Tracker and sets the op inputs of the Simulator without parsing
+/// anything at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstSong {
+    pub lpb:           usize,
+    pub tpl:           usize,
+    pub tick_interval: usize,
+    pub seed:          u64,
+    pub groove:        Option<usize>,
+    pub grooves:       &'static [(&'static str, &'static [f32])],
+    pub instruments:   &'static [ConstInstrument],
+    pub tracks:        &'static [ConstTrack],
+    pub op_inputs:     &'static [(&'static str, &'static [(&'static str, OpIn)])],
+}
+
+impl ConstSong {
+    /// Creates a Tracker that plays the song.
+    pub fn new_tracker<SYNC>(&self, sync: SYNC) -> Tracker<SYNC> where SYNC: TrackerSync {
+        let mut t = Tracker::new(sync);
+        t.lpb           = self.lpb;
+        t.tpl           = self.tpl;
+        t.tick_interval = self.tick_interval;
+        t.set_seed(self.seed);
+
+        for (i, (name, offsets)) in self.grooves.iter().enumerate() {
+            t.set_groove(i, Groove::new(name, offsets.to_vec()));
+        }
+        t.set_song_groove(self.groove);
+
+        for (i, instr) in self.instruments.iter().enumerate() {
+            t.set_instrument((i + 1) as u8, instr.to_instrument());
+        }
+
+        for track in self.tracks.iter() {
+            t.add_track(track.to_track());
+        }
+        t
+    }
+
+    /// Sets the op inputs of the song, the ops have to be
+    /// added to the Simulator before.
+    pub fn setup_simulator(&self, sim: &mut Simulator) {
+        for (op_name, inputs) in self.op_inputs.iter() {
+            if let Some(op_idx) = sim.get_op_index(op_name) {
+                for (in_name, v) in inputs.iter() {
+                    sim.set_op_input(op_idx, in_name, v.clone(), true);
+                    sim.set_op_input(op_idx, in_name, v.clone(), false);
+                }
+            }
+        }
+    }
+}
+
+/// Writes a float literal that reads back as the same f32.
+fn f32lit(v: f32) -> String {
+    if v.is_finite() { format!("{:?}", v) } else { String::from("0.0") }
+}
+
+fn int2rust(int: Interpolation) -> &'static str {
+    match int {
+        Interpolation::Empty => "Interpolation::Empty",
+        Interpolation::Step  => "Interpolation::Step",
+        Interpolation::Lerp  => "Interpolation::Lerp",
+        Interpolation::SStep => "Interpolation::SStep",
+        Interpolation::Exp   => "Interpolation::Exp",
+    }
+}
+
+fn cond2rust(c: Condition) -> String {
+    match c {
+        Condition::Always         => String::from("Condition::Always"),
+        Condition::Probability(p) => format!("Condition::Probability({})", p),
+        Condition::EveryNth(n)    => format!("Condition::EveryNth({})", n),
+        Condition::FirstPass      => String::from("Condition::FirstPass"),
+        Condition::NotFirstPass   => String::from("Condition::NotFirstPass"),
+    }
+}
+
+fn kind2rust(kind: TrackKind) -> String {
+    match kind {
+        TrackKind::Mixed   => String::from("TrackKind::Mixed"),
+        TrackKind::Note    => String::from("TrackKind::Note"),
+        TrackKind::Trigger => String::from("TrackKind::Trigger"),
+        TrackKind::Automation { min, max } =>
+            format!("TrackKind::Automation {{ min: {}, max: {} }}", f32lit(min), f32lit(max)),
+    }
+}
+
+fn value2rust(v: Option<(f32, Interpolation)>) -> String {
+    match v {
+        Some((v, int)) => format!("Some(({}, {}))", f32lit(v), int2rust(int)),
+        None           => String::from("None"),
+    }
+}
+
+fn row2rust(line: usize, row: &Row) -> String {
+    let mut values : Vec<Option<(f32, Interpolation)>> =
+        (0..(row.more_values.len() + 1)).map(|col| row.value_at(col)).collect();
+    while let Some(None) = values.last() { values.pop(); }
+
+    let values : Vec<String> = values.into_iter().map(value2rust).collect();
+    let slide =
+        match row.slide {
+            Some((lines, int)) => format!("Some(({}, {}))", lines, int2rust(int)),
+            None               => String::from("None"),
+        };
+
+    format!("ConstRow {{ line: {}, note: {}, instr: {}, a: {}, b: {}, values: &[{}], cond: {}, slide: {} }}",
+            line, row.note, row.instr, row.a, row.b,
+            values.join(", "), cond2rust(row.cond), slide)
+}
+
+fn track2rust(idx: usize, t: &Track, out: &mut String) {
+    for (pat_idx, pat) in t.patterns.iter().enumerate() {
+        *out += &format!("const T{}_P{} : &[ConstRow] = &[\n", idx, pat_idx);
+        for (line, row) in pat.iter() {
+            *out += &format!("    {},\n", row2rust(*line, row));
+        }
+        *out += "];\n";
+    }
+
+    let patterns : Vec<String> =
+        t.patterns.iter().enumerate()
+            .map(|(pat_idx, pat)|
+                format!("ConstPattern {{ len: {}, rows: T{}_P{} }}", pat.len(), idx, pat_idx))
+            .collect();
+    let arrangement : Vec<String> = t.arrangement.iter().map(|p| p.to_string()).collect();
+
+    *out += &format!("const T{} : ConstTrack = ConstTrack {{\n", idx);
+    *out += &format!("    name:          {:?},\n", t.name);
+    *out += &format!("    kind:          {},\n", kind2rust(t.kind));
+    *out += &format!("    lpp:           {},\n", t.lpp);
+    *out += &format!("    value_columns: {},\n", t.value_columns);
+    *out += &format!("    mute:          {},\n", t.mute);
+    *out += &format!("    solo:          {},\n", t.solo);
+    *out += &format!("    groove:        {:?},\n", t.groove);
+    *out += &format!("    patterns:      &[{}],\n", patterns.join(", "));
+    *out += &format!("    arrangement:   &[{}],\n", arrangement.join(", "));
+    *out += "};\n\n";
+}
+
+fn instrument2rust(instr: &Instrument) -> String {
+    let groups : Vec<String> = instr.groups.iter().map(|g| g.to_string()).collect();
+    let params : Vec<String> =
+        instr.params.iter()
+            .map(|(op, inp, v)| format!("({:?}, {:?}, {})", op, inp, f32lit(*v)))
+            .collect();
+    let freq_input =
+        match &instr.freq_input {
+            Some((op, inp)) => format!("Some(({:?}, {:?}))", op, inp),
+            None            => String::from("None"),
+        };
+
+    format!("ConstInstrument {{ name: {:?}, groups: &[{}], params: &[{}], freq_input: {} }}",
+            instr.name, groups.join(", "), params.join(", "), freq_input)
+}
+
+/// Writes an op input value in the JSON form of the project file,
+/// like `{"RegAdd":[1,0.5]}`, as `OpIn::RegAdd(1, 0.5)`.
+fn opin2rust(v: &Value) -> Result<String, String> {
+    let bad = || format!("bad op input value {}", v);
+    let (typ, args) = v.as_object().and_then(|o| o.iter().next()).ok_or_else(bad)?;
+
+    let num = |n: &Value| -> Result<String, String> {
+        if let Some(i) = n.as_u64() { return Ok(i.to_string()); }
+        n.as_f64().map(|f| f32lit(f as f32)).ok_or_else(bad)
+    };
+
+    let args : Vec<String> =
+        match args {
+            Value::Array(a) => a.iter().map(num).collect::<Result<Vec<String>, String>>()?,
+            Value::Null     => vec![],
+            n               => vec![num(n)?],
+        };
+
+    if args.is_empty() {
+        Ok(format!("OpIn::{}", typ))
+    } else {
+        Ok(format!("OpIn::{}({})", typ, args.join(", ")))
+    }
+}
+
+fn op_inputs2rust(op_inputs: &Value, out: &mut String) -> Result<(), String> {
+    let bad = || String::from("bad op input values");
+
+    *out += "const OP_INPUTS : &[(&str, &[(&str, OpIn)])] = &[\n";
+    for op in op_inputs.as_array().ok_or_else(bad)?.iter() {
+        let op_name = op.get(0).and_then(|n| n.as_str()).ok_or_else(bad)?;
+        let inputs  = op.get(1).and_then(|i| i.as_array()).ok_or_else(bad)?;
+
+        *out += &format!("    ({:?}, &[\n", op_name);
+        for inp in inputs.iter() {
+            let in_name = inp.get(0).and_then(|n| n.as_str()).ok_or_else(bad)?;
+            let v       = inp.get(1).ok_or_else(bad)?;
+            *out += &format!("        ({:?}, {}),\n", in_name, opin2rust(v)?);
+        }
+        *out += "    ]),\n";
+    }
+    *out += "];\n\n";
+
+    Ok(())
+}
+
+/// Generates Rust source of the song, for embedding it into a demo
+/// without parsing at runtime. The patterns, arrangements, instruments,
+/// grooves and op inputs become constants and the constant `SONG` (see
+/// ConstSong) rebuilds the Tracker and sets up the Simulator.
+/// `op_inputs` are the op input values in their JSON form as stored in
+/// the project file. The tuning and the scale are not written.
+pub fn song_to_rust<SYNC>(tracker: &Tracker<SYNC>, op_inputs: &Value) -> Result<String, String>
+    where SYNC: TrackerSync {
+
+    let mut out = String::from("// Generated by wdem-tracker, do not edit.\n");
+    out += "#![allow(dead_code, unused_imports)]\n";
+    out += "use wdem_tracker::track::{TrackKind, Interpolation, Condition};\n";
+    out += "use wdem_tracker::song_source::*;\n";
+    out += "use wctr_signal_ops::signals::OpIn;\n\n";
+
+    for (idx, t) in tracker.tracks.iter().enumerate() {
+        track2rust(idx, t, &mut out);
+    }
+
+    op_inputs2rust(op_inputs, &mut out)?;
+
+    let tracks : Vec<String> =
+        (0..tracker.tracks.len()).map(|idx| format!("T{}", idx)).collect();
+    let grooves : Vec<String> =
+        tracker.grooves.iter()
+            .map(|g| {
+                let offsets : Vec<String> = g.offsets.iter().map(|o| f32lit(*o)).collect();
+                format!("({:?}, &[{}])", g.name, offsets.join(", "))
+            })
+            .collect();
+
+    out += "pub const SONG : ConstSong = ConstSong {\n";
+    out += &format!("    lpb:           {},\n", tracker.lpb);
+    out += &format!("    tpl:           {},\n", tracker.tpl);
+    out += &format!("    tick_interval: {},\n", tracker.tick_interval);
+    out += &format!("    seed:          {},\n", tracker.seed);
+    out += &format!("    groove:        {:?},\n", tracker.groove);
+    out += &format!("    grooves:       &[{}],\n", grooves.join(", "));
+    out += "    instruments:   &[\n";
+    for instr in tracker.instruments.iter() {
+        out += &format!("        {},\n", instrument2rust(instr));
+    }
+    out += "    ],\n";
+    out += &format!("    tracks:        &[{}],\n", tracks.join(", "));
+    out += "    op_inputs:     OP_INPUTS,\n";
+    out += "};\n";
+
+    Ok(out)
+}
@@ -0,0 +1,239 @@
+use std::io::{Read, Write, ErrorKind};
+use std::net::{TcpListener, TcpStream, SocketAddr};
+use crate::track::Track;
+
+/// The version of the protocol, sent in the `hello` line.
+pub const SYNC_PROTOCOL_VERSION : usize = 1;
+
+/// Clients that do not read their data are disconnected
+/// when this much data is waiting for them.
+const MAX_PENDING_BYTES : usize = 1024 * 1024;
+
+/// A command that a client sent to the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncCommand {
+    Play,
+    Pause,
+    /// Continue playing at the line.
+    Seek(usize),
+}
+
+fn parse_command(line: &str) -> Result<SyncCommand, String> {
+    let mut parts = line.split_whitespace();
+    match parts.next() {
+        Some("play")  => Ok(SyncCommand::Play),
+        Some("pause") => Ok(SyncCommand::Pause),
+        Some("seek")  => {
+            match parts.next().and_then(|l| l.parse::<usize>().ok()) {
+                Some(line) => Ok(SyncCommand::Seek(line)),
+                None       => Err(format!("bad line in '{}'", line)),
+            }
+        },
+        _ => Err(format!("unknown command '{}'", line)),
+    }
+}
+
+struct SyncClient {
+    stream:  TcpStream,
+    /// Received bytes that do not form a complete line yet.
+    input:   Vec<u8>,
+    /// Bytes that could not be sent yet.
+    output:  Vec<u8>,
+    closed:  bool,
+}
+
+impl SyncClient {
+    fn send(&mut self, s: &str) {
+        self.output.extend_from_slice(s.as_bytes());
+        if self.output.len() > MAX_PENDING_BYTES {
+            self.closed = true;
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.output.is_empty() && !self.closed {
+            match self.stream.write(&self.output) {
+                Ok(0) => { self.closed = true; },
+                Ok(n) => { self.output.drain(0..n); },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => { self.closed = true; },
+            }
+        }
+    }
+
+    /// Reads the available data and returns the complete lines.
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut buf = [0u8; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => { self.closed = true; break; },
+                Ok(n) => { self.input.extend_from_slice(&buf[0..n]); },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => { self.closed = true; break; },
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.input.iter().position(|b| *b == b'\n') {
+            let line : Vec<u8> = self.input.drain(0..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+        if self.input.len() > MAX_PENDING_BYTES {
+            self.closed = true;
+        }
+        lines
+    }
+}
+
+/// A TCP server that streams the play position, the song position
+/// and the output values of the tracks to demo engines, for live
+/// editing the visuals while the song plays. The clients can send
+/// play, pause and seek commands back.
+///
+/// The protocol is text based, each message is one line ending
+/// with `\n`, the fields are separated by spaces:
+///
+/// Server to client:
+///
+/// - `hello wdem-tracker <version>`: sent after connecting.
+/// - `outputs <count>`, followed by `count` lines `out <index> <name>`:
+///   the names of the output values, the name is the rest of the line.
+///   Sent after connecting and whenever the tracks changed.
+/// - `frame <line> <song pos in seconds> <playing 0/1> <values>...`: the
+///   play state and the output values, sent whenever the song advanced.
+/// - `error <message>`: a command was not understood.
+///
+/// Client to server:
+///
+/// - `play` and `pause`: start and stop playing.
+/// - `seek <line>`: go to the line.
+pub struct SyncServer {
+    listener: TcpListener,
+    clients:  Vec<SyncClient>,
+    outputs:  Vec<(usize, String)>,
+}
+
+impl SyncServer {
+    /// Starts listening, for example on `127.0.0.1:9930`.
+    /// Port 0 picks a free port, see local_addr().
+    pub fn bind(addr: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(SyncServer { listener, clients: Vec::new(), outputs: Vec::new() })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn client_count(&self) -> usize { self.clients.len() }
+
+    fn outputs_msg(&self) -> String {
+        let mut s = format!("outputs {}\n", self.outputs.len());
+        for (idx, name) in self.outputs.iter() {
+            s += &format!("out {} {}\n", idx, name);
+        }
+        s
+    }
+
+    /// Sets the indices of the output values in the frames and
+    /// their names, the clients are only told if they changed.
+    pub fn set_outputs(&mut self, names: Vec<(usize, String)>) {
+        if names == self.outputs { return; }
+        self.outputs = names;
+
+        let msg = self.outputs_msg();
+        for c in self.clients.iter_mut() {
+            c.send(&msg);
+            c.flush();
+        }
+    }
+
+    /// Accepts new clients and returns the commands that
+    /// the clients sent since the last call.
+    pub fn poll(&mut self) -> Vec<SyncCommand> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if stream.set_nonblocking(true).is_err() { continue; }
+                    let _ = stream.set_nodelay(true);
+                    println!("SYNC SERVER: client connected {}", addr);
+
+                    let mut c =
+                        SyncClient {
+                            stream,
+                            input:  Vec::new(),
+                            output: Vec::new(),
+                            closed: false,
+                        };
+                    c.send(&format!("hello wdem-tracker {}\n", SYNC_PROTOCOL_VERSION));
+                    c.send(&self.outputs_msg());
+                    c.flush();
+                    self.clients.push(c);
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("SYNC SERVER: accept error: {}", e);
+                    break;
+                },
+            }
+        }
+
+        let mut cmds = Vec::new();
+        for c in self.clients.iter_mut() {
+            for line in c.read_lines() {
+                if line.is_empty() { continue; }
+                match parse_command(&line) {
+                    Ok(cmd) => cmds.push(cmd),
+                    Err(e)  => c.send(&format!("error {}\n", e)),
+                }
+            }
+            c.flush();
+        }
+        self.clients.retain(|c| !c.closed);
+
+        cmds
+    }
+
+    /// Sends the play line, the song position, whether the song plays
+    /// and the output values to all clients.
+    pub fn send_frame(&mut self, play_line: i32, song_pos_s: f32, playing: bool, values: &[f32]) {
+        if self.clients.is_empty() { return; }
+
+        let mut msg =
+            format!("frame {} {} {}", play_line, song_pos_s, if playing { 1 } else { 0 });
+        for v in values.iter() {
+            msg += &format!(" {}", v);
+        }
+        msg += "\n";
+
+        for c in self.clients.iter_mut() {
+            c.send(&msg);
+            c.flush();
+        }
+        self.clients.retain(|c| !c.closed);
+    }
+}
+
+/// Returns the indices of the output values of the tracks (see
+/// Tracker::assign_output_slots()) with their names, ordered by index.
+/// The outputs of tracks with more than one value column are named
+/// `name:1`, `name:2` and so on, like the sync tracks
+/// (see sync_track::SyncTracks::from_tracks()).
+pub fn output_names(tracks: &[Track]) -> Vec<(usize, String)> {
+    let mut names = Vec::new();
+    for t in tracks.iter() {
+        let count = t.output_count();
+        for (col, slot) in t.out_slots.iter().enumerate().take(count) {
+            if count > 1 {
+                names.push((*slot, format!("{}:{}", t.name, col + 1)));
+            } else {
+                names.push((*slot, t.name.clone()));
+            }
+        }
+    }
+    names.sort_by_key(|(slot, _)| *slot);
+    names
+}
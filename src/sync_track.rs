@@ -0,0 +1,217 @@
+use serde::Serialize;
+use serde::Deserialize;
+use crate::track::*;
+
+/// The key types of GNU Rocket in the order of their numbers
+/// in the `.track` files.
+const ROCKET_STEP   : u8 = 0;
+const ROCKET_LINEAR : u8 = 1;
+const ROCKET_SMOOTH : u8 = 2;
+const ROCKET_RAMP   : u8 = 3;
+
+/// A key of a sync track, the interpolation goes to the next key.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SyncKey {
+    pub line:  usize,
+    pub value: f32,
+    pub int:   Interpolation,
+}
+
+/// The keys of one value column of a track, for driving graphics
+/// with the same curves the tracker outputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncTrack {
+    pub name:  String,
+    /// Number of lines of the track. After the last key the
+    /// value goes to 0.0 at the last line, like in the tracker.
+    pub lines: usize,
+    /// The output range of automation tracks.
+    pub range: Option<(f32, f32)>,
+    pub keys:  Vec<SyncKey>,
+}
+
+impl SyncTrack {
+    /// Collects the keys of the value column `col` of the track.
+    /// `name` is the name of the sync track.
+    pub fn from_track(t: &Track, col: usize, name: &str) -> Self {
+        let mut keys  = Vec::new();
+        let mut start = 0;
+        for pat_idx in t.arrangement.iter() {
+            let pat = &t.patterns[*pat_idx];
+            for (idx, row) in pat.iter() {
+                if let Some((value, int)) = row.value_at(col) {
+                    keys.push(SyncKey { line: start + idx, value, int });
+                }
+            }
+            start += pat.len();
+        }
+
+        let range =
+            match t.kind {
+                TrackKind::Automation { min, max } => Some((min, max)),
+                _ => None,
+            };
+
+        SyncTrack { name: name.to_string(), lines: t.line_count(), range, keys }
+    }
+
+    /// Returns the value at the line and the fraction of the line
+    /// that passed. Gives the same values as Track::get_output_value().
+    pub fn value(&self, line: usize, fract_next_line: f64) -> f32 {
+        let end_line = if self.lines > 0 { self.lines - 1 } else { 0 };
+
+        // The next key is at or after the line, the previous key before it:
+        let i =
+            match self.keys.binary_search_by_key(&line, |k| k.line) {
+                Ok(i)  => i,
+                Err(i) => i,
+            };
+        let prev = if i > 0 { self.keys.get(i - 1) } else { None };
+        let next = self.keys.get(i);
+
+        let (a, line_b, val_b) =
+            match (prev, next) {
+                (Some(p), Some(n)) => (p, n.line, n.value),
+                (None,    Some(n)) => (n, end_line, 0.0),
+                (Some(p), None)    => (p, end_line, 0.0),
+                (None,    None)    => return 0.0,
+            };
+        if line < a.line { return 0.0; }
+
+        let v = a.int.value(a.line, a.value, line_b, val_b, line, fract_next_line);
+        match self.range {
+            Some((min, _)) if v < min => min,
+            Some((_, max)) if v > max => max,
+            _                         => v,
+        }
+    }
+
+    /// Writes the keys to a GNU Rocket `.track` file: the number of
+    /// keys and for each key the row, the value and the key type,
+    /// little endian. One row is one line.
+    ///
+    /// Rocket has no key type for Interpolation::Empty, which outputs 0.0,
+    /// it is written as a step to 0.0 on the next line. A step to 0.0 is
+    /// written before the first key and a key with 0.0 on the last line,
+    /// so the value goes to 0.0 after the last key like in the tracker.
+    /// The range of automation tracks is applied to the values of
+    /// the keys only.
+    pub fn to_rocket(&self) -> Vec<u8> {
+        let clamp = |v: f32| {
+            match self.range {
+                Some((min, _)) if v < min => min,
+                Some((_, max)) if v > max => max,
+                _                         => v,
+            }
+        };
+
+        let mut keys : Vec<(usize, f32, u8)> = Vec::new();
+        if let Some(first) = self.keys.first() {
+            if first.line > 0 { keys.push((0, 0.0, ROCKET_STEP)); }
+        }
+
+        for (i, k) in self.keys.iter().enumerate() {
+            let next_line = self.keys.get(i + 1).map(|n| n.line).unwrap_or(self.lines);
+            let typ =
+                match k.int {
+                    Interpolation::Empty => ROCKET_STEP,
+                    Interpolation::Step  => ROCKET_STEP,
+                    Interpolation::Lerp  => ROCKET_LINEAR,
+                    Interpolation::SStep => ROCKET_SMOOTH,
+                    Interpolation::Exp   => ROCKET_RAMP,
+                };
+            keys.push((k.line, clamp(k.value), typ));
+
+            if k.int == Interpolation::Empty && k.line + 1 < next_line {
+                keys.push((k.line + 1, 0.0, ROCKET_STEP));
+            }
+        }
+
+        if let Some(last) = self.keys.last() {
+            if self.lines > 0 && last.line + 1 < self.lines {
+                keys.push((self.lines - 1, 0.0, ROCKET_STEP));
+            }
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+        for (row, value, typ) in keys.iter() {
+            out.extend_from_slice(&(*row as u32).to_le_bytes());
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+            out.push(*typ);
+        }
+        out
+    }
+}
+
+/// The sync tracks of a song, with the timing for sampling
+/// them by the song position in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncTracks {
+    pub lpb:     usize,
+    /// Length of a line in milliseconds.
+    pub line_ms: f32,
+    pub tracks:  Vec<SyncTrack>,
+}
+
+impl SyncTracks {
+    /// Creates a sync track for each value column of the tracks
+    /// with values. The sync tracks are named like the tracks, the
+    /// columns of tracks with more than one value column
+    /// are named `name:1`, `name:2` and so on.
+    pub fn from_tracks(tracks: &[Track], lpb: usize, tpl: usize, tick_interval: usize) -> Self {
+        let mut sync_tracks = Vec::new();
+        for t in tracks.iter() {
+            if !t.kind.has_values() { continue; }
+
+            for col in 0..t.value_columns {
+                let name =
+                    if t.value_columns > 1 { format!("{}:{}", t.name, col + 1) }
+                    else                   { t.name.clone() };
+                sync_tracks.push(SyncTrack::from_track(t, col, &name));
+            }
+        }
+
+        SyncTracks {
+            lpb,
+            line_ms: (tpl * tick_interval) as f32,
+            tracks:  sync_tracks,
+        }
+    }
+
+    pub fn track(&self, name: &str) -> Option<&SyncTrack> {
+        self.tracks.iter().find(|t| t.name == name)
+    }
+
+    /// Returns the value of the sync track `name` at the song
+    /// position in seconds, 0.0 if there is no such track.
+    pub fn value(&self, name: &str, song_pos_s: f32) -> f32 {
+        let t =
+            match self.track(name) {
+                Some(t) => t,
+                None    => return 0.0,
+            };
+        if self.line_ms <= 0.0 { return t.value(0, 0.0); }
+
+        // A bit more than the position, so that the start of a line
+        // is not rounded down to the line before:
+        let pos = (song_pos_s as f64 * 1000.0) / self.line_ms as f64 + 1e-6;
+        let pos = if pos < 0.0 { 0.0 } else { pos };
+        t.value(pos.floor() as usize, pos.fract())
+    }
+}
+
+/// Returns the file name of a Rocket `.track` file like Rocket does:
+/// `base_name.track`, with every byte of the name that is not a letter,
+/// a digit, `.` or `_` written as `-` and its hex code.
+pub fn rocket_track_filename(base: &str, name: &str) -> String {
+    let mut s = format!("{}_", base);
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'.' || b == b'_' {
+            s.push(b as char);
+        } else {
+            s += &format!("-{:02X}", b);
+        }
+    }
+    s + ".track"
+}
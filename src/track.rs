@@ -1,6 +1,9 @@
 use serde::Serialize;
 use serde::Deserialize;
 use crate::gui_painter::GUIPainter;
+use crate::tuning::note_name;
+use crate::scale::Scale;
+use crate::pattern::Pattern;
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Interpolation {
@@ -15,6 +18,168 @@ impl std::default::Default for Interpolation {
     fn default() -> Self { Interpolation::Empty }
 }
 
+impl Interpolation {
+    /// Returns the value at the line and the fraction of the line
+    /// that passed, between the value `val_a` at `line_a` and
+    /// `val_b` at `line_b`.
+    pub fn value(&self, line_a: usize, val_a: f32, line_b: usize, val_b: f32,
+                 line: usize, fract_next_line: f64) -> f32 {
+        let mut diff = line_b - line_a;
+        if diff == 0 { diff = 1; }
+        let diff = diff as f64;
+        let line_f = line as f64 + fract_next_line;
+
+        match self {
+            Interpolation::Empty => 0.0,
+            Interpolation::Step => {
+                if line == line_b {
+                    val_b
+                } else {
+                    val_a
+                }
+            },
+            Interpolation::Lerp => {
+                let x = (line_f - (line_a as f64)) / diff;
+                (  val_a as f64 * (1.0 - x)
+                 + val_b as f64 * x)
+                as f32
+            },
+            Interpolation::SStep => {
+                let x = (line_f - (line_a as f64)) / diff;
+                let x = if x < 0.0 { 0.0 } else { x };
+                let x = if x > 1.0 { 1.0 } else { x };
+                let x = x * x * (3.0 - 2.0 * x);
+
+                (  val_a as f64 * (1.0 - x)
+                 + val_b as f64 * x)
+                as f32
+            },
+            Interpolation::Exp => {
+                let x = (line_f - (line_a as f64)) / diff;
+                let x = x * x;
+
+                (  val_a as f64 * (1.0 - x)
+                 + val_b as f64 * x)
+                as f32
+            },
+        }
+    }
+}
+
+/// The kind of a track determines which columns are shown
+/// in the editor and what the track outputs while playing.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TrackKind {
+    /// Note, value and the A/B columns. Generates note events
+    /// and outputs the interpolated value.
+    Mixed,
+    /// Note and the A/B columns. Generates note events and
+    /// outputs the pitch of the playing note as note number,
+    /// which glides during slides (see Row::slide).
+    Note,
+    /// A wide value column only. The interpolated value output is
+    /// clamped to the range `min` to `max`. Generates no note events.
+    Automation { min: f32, max: f32 },
+    /// A trigger column only. A row with a non zero A value
+    /// outputs a pulse of the height A/255 for the length of its line.
+    Trigger,
+}
+
+impl std::default::Default for TrackKind {
+    fn default() -> Self { TrackKind::Mixed }
+}
+
+impl TrackKind {
+    /// Width of the track in the editor with one value column,
+    /// without the line number column.
+    pub fn width(&self) -> f32 {
+        match self {
+            TrackKind::Mixed            => TRACK_WIDTH,
+            TrackKind::Note             => 130.0,
+            TrackKind::Automation { .. } => 130.0,
+            TrackKind::Trigger          => 70.0,
+        }
+    }
+
+    /// Width of each additional value column in the editor.
+    pub fn value_column_width(&self) -> f32 {
+        match self {
+            TrackKind::Mixed             => 50.0,
+            TrackKind::Automation { .. } => 80.0,
+            _                            => 0.0,
+        }
+    }
+
+    pub fn has_notes(&self) -> bool {
+        match self {
+            TrackKind::Mixed | TrackKind::Note => true,
+            _ => false,
+        }
+    }
+
+    pub fn has_values(&self) -> bool {
+        match self {
+            TrackKind::Mixed | TrackKind::Automation { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// A condition that decides whether the note or trigger of a row
+/// is played on the current pass through the song.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Condition {
+    Always,
+    /// Played with the probability in percent (0 to 100).
+    Probability(u8),
+    /// Played on the first pass and then on every Nth pass.
+    EveryNth(u8),
+    FirstPass,
+    NotFirstPass,
+}
+
+impl std::default::Default for Condition {
+    fn default() -> Self { Condition::Always }
+}
+
+impl Condition {
+    pub fn is_always(&self) -> bool { *self == Condition::Always }
+
+    /// Returns true if the row is played. `pass` counts the passes
+    /// through the song starting at 0, `rnd` is a random number
+    /// from 0.0 to 1.0.
+    pub fn check(&self, pass: usize, rnd: f64) -> bool {
+        match self {
+            Condition::Always         => true,
+            Condition::Probability(p) => rnd * 100.0 < *p as f64,
+            Condition::EveryNth(n)    => *n <= 1 || pass % (*n as usize) == 0,
+            Condition::FirstPass      => pass == 0,
+            Condition::NotFirstPass   => pass > 0,
+        }
+    }
+
+    /// The character that marks the condition in the editor.
+    fn marker(&self) -> char {
+        match self {
+            Condition::Always         => ' ',
+            Condition::Probability(_) => '?',
+            Condition::EveryNth(_)    => '%',
+            Condition::FirstPass      => '1',
+            Condition::NotFirstPass   => '!',
+        }
+    }
+}
+
+/// What a track plays after the next bar boundary in performance mode,
+/// see Tracker::queue_launch().
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Launch {
+    /// Loop the pattern with this index.
+    Pattern(usize),
+    /// Go back to playing the arrangement.
+    Arrangement,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 struct InterpolationState {
     line_a: usize,
@@ -35,50 +200,171 @@ pub struct Row {
     pub a: u8,
     pub b: u8,
     pub note: u8,
+    /// The instrument number, 0 means the last instrument
+    /// used in the track.
+    #[serde(default)]
+    pub instr: u8,
+    /// The values of the value columns after the first one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub more_values: Vec<Option<(f32, Interpolation)>>,
+    /// Decides on which passes the note or trigger is played.
+    #[serde(default, skip_serializing_if = "Condition::is_always")]
+    pub cond: Condition,
+    /// If set, the note does not start a new note, instead the pitch
+    /// of the playing note slides to it over the given number of lines
+    /// with the given interpolation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide: Option<(u8, Interpolation)>,
 }
 
 impl Row {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Row {
             value: None,
             a: 0,
             b: 0,
             note: 0,
+            instr: 0,
+            more_values: Vec::new(),
+            cond: Condition::Always,
+            slide: None,
+        }
+    }
+
+    /// Returns the value of the value column `col`.
+    pub fn value_at(&self, col: usize) -> Option<(f32, Interpolation)> {
+        if col == 0 { return self.value; }
+        self.more_values.get(col - 1).and_then(|v| *v)
+    }
+
+    pub fn set_value_at(&mut self, col: usize, v: Option<(f32, Interpolation)>) {
+        if col == 0 {
+            self.value = v;
+            return;
+        }
+
+        if self.more_values.len() < col {
+            self.more_values.resize(col, None);
         }
+        self.more_values[col - 1] = v;
+        self.trim_values();
+    }
+
+    /// Removes the empty value columns at the end, so that
+    /// a row without any values is equal to Row::new().
+    pub fn trim_values(&mut self) {
+        while let Some(None) = self.more_values.last() {
+            self.more_values.pop();
+        }
+    }
+
+    /// Returns true if any value column of this row has a value.
+    pub fn has_any_value(&self) -> bool {
+        self.value.is_some() || self.more_values.iter().any(|v| v.is_some())
+    }
+
+    /// Copies the columns of `other` into this row that are
+    /// still empty here.
+    fn merge_empty_from(&mut self, other: &Row) {
+        for col in 0..(other.more_values.len() + 1) {
+            if self.value_at(col).is_none() {
+                self.set_value_at(col, other.value_at(col));
+            }
+        }
+        if self.note == 0       { self.note  = other.note; }
+        if self.instr == 0      { self.instr = other.instr; }
+        if self.a == 0          { self.a     = other.a; }
+        if self.b == 0          { self.b     = other.b; }
+        if self.cond.is_always() { self.cond = other.cond; }
+        if self.slide.is_none()  { self.slide = other.slide; }
     }
 
     pub fn draw<P>(&self, p: &mut P, state: &mut GUIState, line: usize) where P: GUIPainter {
-        let val_s =
-            if let Some((val, int)) = self.value {
-                format!("{:>6.2}{}",
-                    val,
-                    match int {
-                        Interpolation::Empty => "e",
-                        Interpolation::Step  => "_",
-                        Interpolation::Lerp  => "/",
-                        Interpolation::SStep => "~",
-                        Interpolation::Exp   => "^",
-                    })
-            } else {
-                String::from("------ ")
-            };
+        let int_s = |int| {
+            match int {
+                Interpolation::Empty => "e",
+                Interpolation::Step  => "_",
+                Interpolation::Lerp  => "/",
+                Interpolation::SStep => "~",
+                Interpolation::Exp   => "^",
+            }
+        };
 
         let note_s = match self.note {
             0 => String::from("---"),
             1 => String::from("off"),
-            n => format!("{:<4}", note2name(n)),
+            n if self.slide.is_some() =>
+                format!("{:<3}>", note2name(n, state)),
+            n => format!("{:<4}", note2name(n, state)),
         };
 
+        let instr_s =
+            if self.instr > 0 { format!("{:02X}", self.instr) }
+            else              { String::from("--") };
+
+        // The value column under the cursor is marked with a '>'.
+        let mut vals_s = String::new();
+        for col in 0..state.value_columns {
+            let cursor_on_col =
+                state.cursor_on_line && col == state.cursor_value_col;
+            if col > 0 || cursor_on_col {
+                vals_s.push(if cursor_on_col { '>' } else { ' ' });
+            }
+
+            let val_s =
+                match (state.track_kind, self.value_at(col)) {
+                    (TrackKind::Automation { .. }, Some((val, int))) =>
+                        format!("{:>10.4}{}", val, int_s(int)),
+                    (TrackKind::Automation { .. }, None) =>
+                        String::from("---------- "),
+                    (_, Some((val, int))) =>
+                        format!("{:>6.2}{}", val, int_s(int)),
+                    (_, None) =>
+                        String::from("------ "),
+                };
+            vals_s += &val_s;
+        }
+
+        let s =
+            match state.track_kind {
+                TrackKind::Mixed => {
+                    format!("|{:<02}|{:<4}{}{}{:>7}|{:02X} {:02X}|",
+                            state.pattern_index,
+                            note_s, self.cond.marker(), instr_s,
+                            vals_s, self.a, self.b)
+                },
+                TrackKind::Note => {
+                    format!("|{:<02}|{:<4}{}{}|{:02X} {:02X}|",
+                            state.pattern_index,
+                            note_s, self.cond.marker(), instr_s,
+                            self.a, self.b)
+                },
+                TrackKind::Automation { .. } => {
+                    format!("|{:<02}|{:>11}|", state.pattern_index, vals_s)
+                },
+                TrackKind::Trigger => {
+                    let trig_s =
+                        if self.a > 0 { format!("x{:02X}", self.a) }
+                        else          { String::from("---") };
+
+                    format!("|{:<02}|{}{}|",
+                            state.pattern_index, trig_s, self.cond.marker())
+                },
+            };
+
         let s =
             if state.track_index == 0 {
-                format!("{:<05} |{:<02}|{:<4}{:>7}|{:02X} {:02X}|",
-                        line,
-                        state.pattern_index,
-                        note_s, val_s, self.a, self.b)
+                format!("{:<05} {}", line, s)
             } else {
-                format!("|{:<02}|{:<4}{:>7}|{:02X} {:02X}|",
-                        state.pattern_index,
-                        note_s, val_s, self.a, self.b)
+                s
+            };
+
+        let in_block =
+            match state.block {
+                Some((t0, t1, l0, l1)) =>
+                       state.track_index >= t0 && state.track_index <= t1
+                    && line >= l0 && line <= l1,
+                None => false,
             };
 
         let color =
@@ -88,12 +374,22 @@ impl Row {
                 [0.8, 0.4, 0.4, 1.0]
             } else if state.cursor_on_line {
                 [0.4, 0.8, 0.4, 1.0]
+            } else if in_block {
+                [0.2, 0.2, 0.5, 1.0]
             } else {
                 [0.0, 0.0, 0.0, 1.0]
             };
 
+        let out_of_scale =
+            match state.scale {
+                Some(scale) if state.note_steps == 12 => !scale.contains(self.note),
+                _ => false,
+            };
+
         let txt_color =
-            if state.cursor_on_line || state.play_on_line {
+            if out_of_scale {
+                [1.0, 0.3, 0.3, 1.0]
+            } else if state.cursor_on_line || state.play_on_line {
                 if state.on_beat { [0.0, 0.4, 0.0, 1.0] }
                 else             { [0.0, 0.0, 0.0, 1.0] }
             } else {
@@ -103,15 +399,24 @@ impl Row {
 
         let width =
             if state.track_index == 0 {
-                FIRST_TRACK_WIDTH
+                state.track_width + LINE_NUM_WIDTH
             } else {
-                TRACK_WIDTH
+                state.track_width
             };
         p.draw_rect(
             color,
             [0.0, 0.0],
             [width, ROW_HEIGHT], true, 0.5);
         p.draw_text(txt_color, [0.0, 0.0], ROW_HEIGHT * 0.9, s);
+
+        if state.pattern_start {
+            p.draw_lines(
+                [0.6, 0.6, 1.0, 1.0],
+                [0.0, 0.0],
+                &[[0.0, 0.0], [width, 0.0]],
+                false,
+                1.0);
+        }
     }
 }
 
@@ -131,42 +436,48 @@ impl InterpolationState {
         self.int = Interpolation::Empty;
     }
 
-    fn to_end(&mut self, l: usize, d: &Row, end_line: usize) {
+    fn to_end(&mut self, l: usize, d: Option<(f32, Interpolation)>, end_line: usize) {
         self.line_a = l;
-        self.val_a  = d.value.unwrap_or((0.0, Interpolation::Step)).0;
-        self.int    = d.value.unwrap_or((0.0, Interpolation::Step)).1;
+        self.val_a  = d.unwrap_or((0.0, Interpolation::Step)).0;
+        self.int    = d.unwrap_or((0.0, Interpolation::Step)).1;
         self.line_b = end_line;
         self.val_b  = 0.0;
     }
 
-    fn to_next(&mut self, l: usize, d: &Row, lb: usize, db: &Row) {
+    fn to_next(&mut self, l: usize, d: Option<(f32, Interpolation)>, lb: usize, db: Option<(f32, Interpolation)>) {
         self.line_a = l;
-        self.val_a  = d.value.unwrap_or((0.0, Interpolation::Step)).0;
-        self.int    = d.value.unwrap_or((0.0, Interpolation::Step)).1;
+        self.val_a  = d.unwrap_or((0.0, Interpolation::Step)).0;
+        self.int    = d.unwrap_or((0.0, Interpolation::Step)).1;
         self.line_b = lb;
-        self.val_b  = db.value.unwrap_or((0.0, Interpolation::Step)).0;
+        self.val_b  = db.unwrap_or((0.0, Interpolation::Step)).0;
     }
 
     fn desync(&mut self) {
         self.clear();
         self.desync = true;
     }
-}
 
-const NOTE_NAMES : &'static [&str] = &["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    /// Returns the interpolated value at the line and the
+    /// fraction of the line that passed.
+    fn value(&self, line: usize, fract_next_line: f64) -> f32 {
+        self.int.value(
+            self.line_a, self.val_a, self.line_b, self.val_b,
+            line, fract_next_line)
+    }
+}
 
-fn note2name(note: u8) -> String {
+/// The note names follow the number of steps of the tuning,
+/// see GUIState::note_steps.
+fn note2name(note: u8, state: &GUIState) -> String {
     if note == 0 { return String::from(""); }
-
-    let octave   : i32   = (note / 12) as i32 - 1;
-    let name_idx : usize = (note % 12) as usize;
-    format!("{}{}", NOTE_NAMES[name_idx], octave)
+    note_name(note, state.note_steps, state.note_middle)
 }
 
 pub const TPOS_PAD      : f32 = 50.0;
 pub const TRACK_PAD     : f32 =  0.0;
-pub const TRACK_WIDTH   : f32 = 160.0;
-pub const FIRST_TRACK_WIDTH : f32 = TRACK_WIDTH + 40.0;
+pub const TRACK_WIDTH   : f32 = 180.0;
+pub const LINE_NUM_WIDTH : f32 = 40.0;
+pub const FIRST_TRACK_WIDTH : f32 = TRACK_WIDTH + LINE_NUM_WIDTH;
 pub const ROW_HEIGHT    : f32 = 15.0;
 pub const ROW_COMPR_FACT : f32 = 0.8;
 pub const CONTEXT_LINES : usize = 6;
@@ -177,43 +488,133 @@ pub struct GUIState {
     pub play_line:          i32,
     pub cursor_on_track:    bool,
     pub track_index:        usize,
+    pub track_kind:         TrackKind,
+    pub track_width:        f32,
+    pub value_columns:      usize,
+    pub cursor_value_col:   usize,
     pub pattern_index:      usize,
+    pub pattern_start:      bool,
     pub play_on_line:       bool,
     pub on_beat:            bool,
     pub lpb:                usize,
     pub cursor_on_line:     bool,
     pub scroll_offs:        usize,
+    /// Number of steps per octave of the tuning and the note number
+    /// of its first step in octave 4, for the note names.
+    pub note_steps:         usize,
+    pub note_middle:        u8,
+    /// Notes outside of this scale are highlighted.
+    pub scale:              Option<Scale>,
+    /// The marked block: first and last track, first and last line.
+    pub block:              Option<(usize, usize, usize, usize)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Track {
     pub name: String,
+    /// One interpolation state per value column.
     #[serde(skip)]
-    interpol: InterpolationState,
+    interpol: Vec<InterpolationState>,
     // if index is at or above desired key, interpolate
     // else set index = 0 and restart search for right key
+    /// Number of lines of newly created patterns. Each pattern
+    /// may have a different length, see set_pattern_len().
     pub lpp:         usize,
-    pub patterns:    Vec<Vec<Row>>,
+    pub patterns:    Vec<Pattern>,
     pub arrangement: Vec<usize>, // arrangement of the patterns
+    #[serde(default)]
+    pub kind:        TrackKind,
+    /// Number of value columns, each has it's own output.
+    #[serde(default = "default_value_columns")]
+    pub value_columns: usize,
+    /// If true, no note events are generated for this track.
+    #[serde(default)]
+    pub mute:        bool,
+    /// If any track is soloed, only the soloed tracks generate note events.
+    #[serde(default)]
+    pub solo:        bool,
+    /// Set when a note off was sent after the track went silent.
+    #[serde(skip)]
+    silenced:        bool,
+    /// Index of the groove template of the Tracker that is used
+    /// for this track. If None, the song groove is used.
+    #[serde(default)]
+    pub groove:      Option<usize>,
+    /// The last line that was played, -1 if none.
+    #[serde(skip, default = "no_played_line")]
+    played_line:     i32,
+    /// Set if the condition of the played line did not pass.
+    #[serde(skip)]
+    line_skipped:    bool,
+    /// The launch that starts at the next bar boundary.
+    #[serde(skip)]
+    pub queued:      Option<Launch>,
+    /// The index of the pattern that is looped instead of the
+    /// arrangement and the song line it was launched at.
+    #[serde(skip)]
+    launched:        Option<(usize, usize)>,
+    /// A line with a recorded note that was already sounded and
+    /// is not played again.
+    #[serde(skip)]
+    sounded_line:    Option<usize>,
+    /// The pitch of the playing note, which is interpolated
+    /// during a slide.
+    #[serde(skip)]
+    pitch:           InterpolationState,
+    /// The registers of the value proxy that receive the outputs of
+    /// this track, one per output. They are assigned by the Tracker
+    /// that plays (see Tracker::assign_output_slots()) and move
+    /// with the track.
+    #[serde(skip)]
+    pub out_slots:   Vec<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrackSerialized {
     pub name:        String,
-    pub patterns:    Vec<Vec<Row>>,
+    #[serde(default)]
+    pub lpp:         usize,
+    pub patterns:    Vec<Pattern>,
     pub arrangement: Vec<usize>, // arrangement of the patterns
+    #[serde(default)]
+    pub kind:        TrackKind,
+    #[serde(default = "default_value_columns")]
+    pub value_columns: usize,
+    #[serde(default)]
+    pub mute:        bool,
+    #[serde(default)]
+    pub solo:        bool,
+    #[serde(default)]
+    pub groove:      Option<usize>,
 }
 
+fn default_value_columns() -> usize { 1 }
+fn no_played_line() -> i32 { -1 }
+
 impl Track {
     pub fn new(name: &str, lpp: usize) -> Self {
-        let mut fp = Vec::new();
-        fp.resize(lpp, Row::new());
+        Self::new_kind(name, lpp, TrackKind::Mixed)
+    }
 
+    pub fn new_kind(name: &str, lpp: usize, kind: TrackKind) -> Self {
         Track {
             name:        String::from(name),
-            interpol:    InterpolationState::new(),
-            patterns:    vec![fp],
+            interpol:    vec![InterpolationState::new()],
+            patterns:    vec![Pattern::new(lpp)],
             arrangement: vec![0],
+            value_columns: 1,
+            kind,
+            mute:        false,
+            solo:        false,
+            silenced:    false,
+            groove:      None,
+            played_line: -1,
+            line_skipped: false,
+            queued:      None,
+            launched:    None,
+            sounded_line: None,
+            pitch:       InterpolationState::new(),
+            out_slots:   Vec::new(),
             lpp,
         }
     }
@@ -256,21 +657,51 @@ impl Track {
 
         let o = p.get_offs();
 
+        let mut name =
+            match self.groove {
+                Some(g) => format!("{} ~{}", self.name, g + 1),
+                None    => self.name.clone(),
+            };
+        if let Some(pat_idx) = self.launched_pattern() {
+            name += &format!(" @{:02}", pat_idx);
+        }
+        match self.queued {
+            Some(Launch::Pattern(pat_idx)) => name += &format!(" >{:02}", pat_idx),
+            Some(Launch::Arrangement)      => name += " >arr",
+            None                           => (),
+        }
+        let (name_color, name) =
+            if self.solo {
+                ([1.0, 1.0, 0.4, 1.0], format!("{} [S]", name))
+            } else if self.mute {
+                ([1.0, 0.4, 0.4, 1.0], format!("{} [M]", name))
+            } else {
+                ([1.0, 1.0, 1.0, 1.0], name)
+            };
+
         p.draw_text(
-            [1.0, 1.0, 1.0, 1.0],
+            name_color,
             [0.0, 0.2 * ROW_HEIGHT],
             0.8 * ROW_HEIGHT,
-            self.name.clone());
+            name);
         p.add_offs(0.0, ROW_HEIGHT);
 
+        let empty = Row::new();
         for l in from..to {
             state.play_on_line   = state.play_line == l as i32;
             state.cursor_on_line = state.cursor_on_track && state.cursor_line == l;
             state.on_beat        = (l % state.lpb) == 0;
 
-            if let Some((pat_idx, row)) = self.row_checked(l) {
+            state.track_kind    = self.kind;
+            state.track_width   = self.width();
+            state.value_columns =
+                if self.kind.has_values() { self.value_columns } else { 0 };
+
+            if let Some((arr_idx, row_idx)) = self.line2pattern_pos(l) {
+                let pat_idx = self.arrangement[arr_idx];
                 state.pattern_index = pat_idx;
-                row.draw(p, state, l);
+                state.pattern_start = row_idx == 0;
+                self.patterns[pat_idx].get(row_idx).unwrap_or(&empty).draw(p, state, l);
             }
 
             p.add_offs(0.0, ROW_HEIGHT * ROW_COMPR_FACT);
@@ -280,23 +711,65 @@ impl Track {
     }
 
     pub fn deserialize_contents(&mut self, ts: &TrackSerialized) {
+        if ts.lpp > 0 {
+            self.lpp = ts.lpp;
+        }
         self.patterns    = ts.patterns.clone();
         self.arrangement = ts.arrangement.clone();
+        self.kind        = ts.kind;
+        self.value_columns = if ts.value_columns < 1 { 1 } else { ts.value_columns };
+        self.mute        = ts.mute;
+        self.solo        = ts.solo;
+        self.groove      = ts.groove;
         self.desync();
     }
 
     pub fn serialize_contents(&self) -> TrackSerialized {
         TrackSerialized {
             name:        self.name.clone(),
+            lpp:         self.lpp,
             patterns:    self.patterns.clone(),
             arrangement: self.arrangement.clone(),
+            kind:        self.kind,
+            value_columns: self.value_columns,
+            mute:        self.mute,
+            solo:        self.solo,
+            groove:      self.groove,
+        }
+    }
+
+    /// Width of the track in the editor, without the line number column.
+    pub fn width(&self) -> f32 {
+        let more_cols =
+            if self.value_columns > 1 { self.value_columns - 1 } else { 0 };
+        self.kind.width() + more_cols as f32 * self.kind.value_column_width()
+    }
+
+    /// Changes the number of value columns. The values of removed
+    /// columns are deleted.
+    pub fn set_value_columns(&mut self, cols: usize) {
+        let cols = if cols < 1 { 1 } else { cols };
+
+        for pat in self.patterns.iter_mut() {
+            pat.edit_rows(|row| {
+                if row.more_values.len() >= cols {
+                    row.more_values.truncate(cols - 1);
+                }
+            });
         }
+
+        self.value_columns = cols;
+        self.desync();
+    }
+
+    /// Number of output values of this track.
+    pub fn output_count(&self) -> usize {
+        if self.kind.has_values() { self.value_columns } else { 1 }
     }
 
     pub fn set_arrangement_pattern(&mut self, line: usize, pat_idx: usize) {
         if pat_idx < self.patterns.len() {
-            let arr_idx = line / self.lpp;
-            while arr_idx >= self.arrangement.len() {
+            while line >= self.line_count() {
                 self.arrangement.push(pat_idx)
             }
         }
@@ -304,128 +777,267 @@ impl Track {
 
     pub fn touch_pattern_idx(&mut self, pat_idx: usize) {
         if pat_idx >= self.patterns.len() {
-            let mut fp = Vec::new();
-            fp.resize(self.lpp, Row::new());
-            self.patterns.resize(pat_idx + 1, fp);
+            self.patterns.resize(pat_idx + 1, Pattern::new(self.lpp));
         }
     }
 
-    pub fn line_count(&self) -> usize {
-        self.arrangement.len() * self.lpp
+    /// Returns the number of lines of the pattern `pat_idx`.
+    pub fn pattern_len(&self, pat_idx: usize) -> usize {
+        if pat_idx >= self.patterns.len() { return 0; }
+        self.patterns[pat_idx].len()
     }
 
-    pub fn row_checked(&self, line: usize) -> Option<(usize, Row)> {
-        if line >= self.line_count() { return None; }
-        Some((
-            self.arrangement[line / self.lpp],
-            self.patterns[self.arrangement[line / self.lpp]][line % self.lpp].clone()
-        ))
+    /// Changes the number of lines of a single pattern. Rows beyond the
+    /// new length are cut off, new rows are empty. A pattern has at least
+    /// one line.
+    pub fn set_pattern_len(&mut self, pat_idx: usize, len: usize) {
+        let len = if len < 1 { 1 } else { len };
+        self.touch_pattern_idx(pat_idx);
+        self.patterns[pat_idx].resize(len);
+        self.desync();
     }
 
-    pub fn row(&mut self, line: usize) -> &mut Row {
-        &mut self.patterns[self.arrangement[line / self.lpp]][line % self.lpp]
+    /// Changes the resolution of the track from `lpp` to `new_lpp`
+    /// lines per pattern. All patterns are scaled by the same factor and
+    /// the existing rows are moved to their new line positions, so
+    /// going from 32 to 64 doubles the line of every row. If multiple rows
+    /// end up on the same line when reducing the resolution, the
+    /// earlier row wins and the later rows only fill in the
+    /// columns that are still empty.
+    pub fn resample_lpp(&mut self, new_lpp: usize) {
+        if new_lpp < 1 || new_lpp == self.lpp { return; }
+        let old_lpp = self.lpp;
+
+        for pat in self.patterns.iter_mut() {
+            let new_len = (pat.len() * new_lpp) / old_lpp;
+            let new_len = if new_len < 1 { 1 } else { new_len };
+
+            let mut new_pat = Pattern::new(new_len);
+
+            for (i, row) in pat.iter() {
+                let ni = (i * new_lpp) / old_lpp;
+                if ni >= new_len { break; }
+                let mut new_row = new_pat.row(ni);
+                new_row.merge_empty_from(row);
+                new_pat.set(ni, new_row);
+            }
+
+            *pat = new_pat;
+        }
+
+        self.lpp = new_lpp;
+        self.desync();
     }
 
-    pub fn prev_row_with_value(&mut self, line: usize) -> Option<(usize, Row)> {
-        let mut ll = line;
-        while ll > 0 {
-            let row = &self.patterns[self.arrangement[(ll - 1) / self.lpp]][(ll - 1) % self.lpp];
-            if (*row).value.is_some() {
-                return Some(((ll - 1), row.clone()));
+    /// Returns the line where the pattern at the arrangement
+    /// index `arr_idx` starts.
+    pub fn arrangement_start_line(&self, arr_idx: usize) -> usize {
+        self.arrangement
+            .iter()
+            .take(arr_idx)
+            .map(|pat_idx| self.patterns[*pat_idx].len())
+            .sum()
+    }
+
+    /// Maps a line of the track to the index into the arrangement
+    /// and the row index inside the pattern at that position.
+    pub fn line2pattern_pos(&self, line: usize) -> Option<(usize, usize)> {
+        let mut start = 0;
+        for (arr_idx, pat_idx) in self.arrangement.iter().enumerate() {
+            let len = self.patterns[*pat_idx].len();
+            if line < start + len {
+                return Some((arr_idx, line - start));
             }
-            ll -= 1;
+            start += len;
         }
 
         None
     }
 
-    pub fn next_row_with_value(&mut self, line: usize) -> Option<(usize, Row)> {
-        let mut ll = line;
-        let lc = self.line_count();
-        while ll < lc {
-            let pat_idx = self.arrangement[ll / self.lpp];
-            let row = &self.patterns[pat_idx][ll % self.lpp];
-            if (*row).value.is_some() {
-                return Some((ll, row.clone()));
+    pub fn line_count(&self) -> usize {
+        self.arrangement_start_line(self.arrangement.len())
+    }
+
+    /// Returns the row at `line`, or None if it is empty
+    /// or outside of the track.
+    fn row_ref(&self, line: usize) -> Option<&Row> {
+        let (arr_idx, row_idx) = self.line2pattern_pos(line)?;
+        self.patterns[self.arrangement[arr_idx]].get(row_idx)
+    }
+
+    pub fn row_checked(&self, line: usize) -> Option<(usize, Row)> {
+        let (arr_idx, row_idx) = self.line2pattern_pos(line)?;
+        Some((
+            self.arrangement[arr_idx],
+            self.patterns[self.arrangement[arr_idx]].row(row_idx)
+        ))
+    }
+
+    /// Returns the last row before `line` with a value in the
+    /// value column `col`. Only the stored rows of the patterns
+    /// are searched, see Pattern.
+    pub fn prev_row_with_value(&mut self, col: usize, line: usize) -> Option<(usize, Row)> {
+        let (mut arr_idx, mut row_idx) =
+            self.line2pattern_pos(line)
+                .unwrap_or((self.arrangement.len(), 0));
+        let mut start = self.arrangement_start_line(arr_idx);
+
+        loop {
+            if let Some(pat_idx) = self.arrangement.get(arr_idx) {
+                let pat = &self.patterns[*pat_idx];
+                if let Some((idx, row)) = pat.prev_with_value(col, row_idx) {
+                    return Some((start + idx, row.clone()));
+                }
+            }
+
+            if arr_idx == 0 { return None; }
+            arr_idx -= 1;
+            row_idx  = self.patterns[self.arrangement[arr_idx]].len();
+            start   -= row_idx;
+        }
+    }
+
+    /// Returns the first row at or after `line` with a value in the
+    /// value column `col`.
+    pub fn next_row_with_value(&mut self, col: usize, line: usize) -> Option<(usize, Row)> {
+        let (arr_idx, row_idx) = self.line2pattern_pos(line)?;
+        let mut start = self.arrangement_start_line(arr_idx);
+        let mut from  = row_idx;
+
+        for pat_idx in self.arrangement.iter().skip(arr_idx) {
+            let pat = &self.patterns[*pat_idx];
+            if let Some((idx, row)) = pat.next_with_value(col, from) {
+                return Some((start + idx, row.clone()));
             }
-            ll += 1;
+            start += pat.len();
+            from   = 0;
         }
 
         None
     }
 
-    pub fn touch_row(&mut self, line: usize) -> &mut Row {
-        let a = line / self.lpp;
-        while a >= self.arrangement.len() {
-            self.patterns.push(Vec::new());
-            let last_idx = self.patterns.len() - 1;
-            self.patterns[last_idx].resize(self.lpp, Row::new());
-            self.arrangement.push(self.patterns.len() - 1);
+    /// Changes the row at `line` with `f`. Patterns are appended
+    /// if the line is after the end of the track. A row that is
+    /// empty afterwards is removed, see Pattern.
+    pub fn edit_row<F>(&mut self, line: usize, f: F) where F: FnOnce(&mut Row) {
+        let mut row =
+            match self.row_checked(line) {
+                Some((_, row)) => row,
+                None           => Row::new(),
+            };
+        f(&mut row);
+
+        if line >= self.line_count() {
+            if row == Row::new() { return; }
+
+            while line >= self.line_count() {
+                self.patterns.push(Pattern::new(self.lpp));
+                self.arrangement.push(self.patterns.len() - 1);
+            }
         }
 
-        &mut self.patterns[self.arrangement[a]][line % self.lpp]
+        let (arr_idx, row_idx) =
+            self.line2pattern_pos(line).expect("line inside of track");
+        self.patterns[self.arrangement[arr_idx]].set(row_idx, row);
     }
 
     pub fn desync(&mut self) {
-        self.interpol.desync();
+        if self.interpol.len() != self.value_columns {
+            self.interpol.resize(self.value_columns, InterpolationState::new());
+        }
+        for i in self.interpol.iter_mut() { i.desync(); }
     }
 
     pub fn remove_value(&mut self, line: usize) {
-        *self.touch_row(line) = Row::new();
+        if let Some((arr_idx, row_idx)) = self.line2pattern_pos(line) {
+            self.patterns[self.arrangement[arr_idx]].remove(row_idx);
+        }
         self.desync();
     }
 
-    pub fn set_int(&mut self, line: usize, int: Interpolation) {
-        if let Some((v, _i)) = (*self.touch_row(line)).value {
-            (*self.touch_row(line)).value = Some((v, int));
-        } else {
-            (*self.touch_row(line)).value = Some((0.0, int));
-        }
+    pub fn set_int(&mut self, col: usize, line: usize, int: Interpolation) {
+        self.edit_row(line, |row| {
+            if let Some((v, _i)) = row.value_at(col) {
+                row.set_value_at(col, Some((v, int)));
+            } else {
+                row.set_value_at(col, Some((0.0, int)));
+            }
+        });
         self.desync();
     }
 
     pub fn set_note(&mut self, line: usize, value: u8) {
-        (*self.touch_row(line)).note = value;
+        self.edit_row(line, |row| row.note = value);
+        self.desync();
+    }
+
+    pub fn set_instr(&mut self, line: usize, value: u8) {
+        self.edit_row(line, |row| row.instr = value);
         self.desync();
     }
 
     pub fn set_a(&mut self, line: usize, value: u8) {
-        (*self.touch_row(line)).a = value;
+        self.edit_row(line, |row| row.a = value);
+        self.desync();
+    }
+
+    pub fn set_slide(&mut self, line: usize, slide: Option<(u8, Interpolation)>) {
+        self.edit_row(line, |row| row.slide = slide);
+        self.desync();
+    }
+
+    pub fn set_cond(&mut self, line: usize, cond: Condition) {
+        self.edit_row(line, |row| row.cond = cond);
         self.desync();
     }
 
     pub fn set_b(&mut self, line: usize, value: u8) {
-        (*self.touch_row(line)).b = value;
+        self.edit_row(line, |row| row.b = value);
         self.desync();
     }
 
-    pub fn set_value(&mut self, line: usize, value: f32) {
-        if let Some((_v, i)) = (*self.touch_row(line)).value {
-            (*self.touch_row(line)).value = Some((value, i));
-        } else {
-            (*self.touch_row(line)).value = Some((value, Interpolation::Step));
-        }
+    pub fn set_row(&mut self, line: usize, row: Row) {
+        self.edit_row(line, |r| *r = row);
         self.desync();
     }
 
-    fn sync_interpol_to_play_line(&mut self, line: usize) -> Option<Row> {
-        if let Some((l_a, row_a)) = self.next_row_with_value(line) {
-            if let Some((l_b, row_b)) = self.prev_row_with_value(line) {
-                self.interpol.to_next(l_b, &row_b, l_a, &row_a);
+    pub fn set_value(&mut self, col: usize, line: usize, value: f32) {
+        self.edit_row(line, |row| {
+            if let Some((_v, i)) = row.value_at(col) {
+                row.set_value_at(col, Some((value, i)));
             } else {
-                self.interpol.to_end(l_a, &row_a, self.line_count() - 1);
+                row.set_value_at(col, Some((value, Interpolation::Step)));
             }
+        });
+        self.desync();
+    }
 
-            if l_a == line { Some(row_a) }
-            else { None }
+    fn sync_interpol_to_play_line(&mut self, line: usize) {
+        if self.interpol.len() != self.value_columns {
+            self.interpol.resize(self.value_columns, InterpolationState::new());
+        }
+
+        for col in 0..self.value_columns {
+            self.sync_col_interpol_to_play_line(col, line);
+        }
+    }
+
+    fn sync_col_interpol_to_play_line(&mut self, col: usize, line: usize) {
+        let end_line = self.line_count() - 1;
+
+        if let Some((l_a, row_a)) = self.next_row_with_value(col, line) {
+            if let Some((l_b, row_b)) = self.prev_row_with_value(col, line) {
+                self.interpol[col].to_next(
+                    l_b, row_b.value_at(col), l_a, row_a.value_at(col));
+            } else {
+                self.interpol[col].to_end(l_a, row_a.value_at(col), end_line);
+            }
         } else {
-            if let Some((l_b, row_b)) = self.prev_row_with_value(line) {
-                self.interpol.to_end(l_b, &row_b, self.line_count() - 1);
+            if let Some((l_b, row_b)) = self.prev_row_with_value(col, line) {
+                self.interpol[col].to_end(l_b, row_b.value_at(col), end_line);
             } else {
-                self.interpol.clear();
+                self.interpol[col].clear();
             }
-
-            None
         }
     }
 
@@ -436,58 +1048,237 @@ impl Track {
     pub fn play_line(&mut self, line: usize) -> Option<Row> {
         self.sync_interpol_to_play_line(line);
 
-        let r = self.row(line);
-        if r.note > 0 { Some(r.clone()) } else { None }
-    }
+        if !self.kind.has_notes() { return None; }
 
-    /// Returns the interpolated value of this track at the specified line.
-    /// Only works if the interpolation was
-    /// initialized with self.sync_interpol_to_play_line() in self.play_line()!
-    pub fn get_value(&mut self, line: usize, fract_next_line: f64) -> f32 {
-        let i = &mut self.interpol;
+        let mut r =
+            match self.row_ref(line) {
+                Some(r) if r.note > 0 => r.clone(),
+                _ => return None,
+            };
 
-        if line < i.line_a {
-            i.clear();
+        if r.note == 1 {
+            self.pitch.clear();
+            return Some(r);
         }
 
-        let mut diff = i.line_b - i.line_a;
-        if diff == 0 { diff = 1; }
-        let diff = diff as f64;
-        let line_f = line as f64 + fract_next_line;
+        let note = r.note as f32;
+        match (r.slide, self.pitch(line, 0.0)) {
+            (Some((lines, int)), Some(from)) => {
+                // A slide changes the pitch of the playing note,
+                // no new note is started:
+                self.pitch.to_next(
+                    line, Some((from, int)),
+                    line + lines as usize, Some((note, int)));
+                None
+            },
+            _ => {
+                self.pitch.to_next(
+                    line, Some((note, Interpolation::Step)),
+                    line, Some((note, Interpolation::Step)));
 
-        match i.int {
-            Interpolation::Empty => 0.0,
-            Interpolation::Step => {
-                if line == i.line_b {
-                    i.val_b
-                } else {
-                    i.val_a
+                if r.instr == 0 {
+                    r.instr = self.instr_at(line);
                 }
+                Some(r)
             },
-            Interpolation::Lerp => {
-                let x = (line_f - (i.line_a as f64)) / diff;
-                (  i.val_a as f64 * (1.0 - x)
-                 + i.val_b as f64 * x)
-                as f32
-            },
-            Interpolation::SStep => {
-                let x = (line_f - (i.line_a as f64)) / diff;
-                let x = if x < 0.0 { 0.0 } else { x };
-                let x = if x > 1.0 { 1.0 } else { x };
-                let x = x * x * (3.0 - 2.0 * x);
+        }
+    }
 
-                (  i.val_a as f64 * (1.0 - x)
-                 + i.val_b as f64 * x)
-                as f32
-            },
-            Interpolation::Exp => {
-                let x = (line_f - (i.line_a as f64)) / diff;
-                let x = x * x;
+    /// Returns the pitch of the playing note as note number,
+    /// or None if no note plays. During a slide the pitch
+    /// is interpolated, see Row::slide.
+    pub fn pitch(&self, line: usize, fract_next_line: f64) -> Option<f32> {
+        let p = &self.pitch;
+        if p.int == Interpolation::Empty { return None; }
 
-                (  i.val_a as f64 * (1.0 - x)
-                 + i.val_b as f64 * x)
-                as f32
+        if line < p.line_a || line >= p.line_b {
+            Some(p.val_b)
+        } else {
+            Some(p.value(line, fract_next_line))
+        }
+    }
+
+    /// Returns true while the pitch slides to the note of a slide.
+    pub fn is_sliding(&self, line: usize) -> bool {
+        self.pitch.int != Interpolation::Empty
+        && self.pitch.line_b > self.pitch.line_a
+        && line >= self.pitch.line_a
+        && line <= self.pitch.line_b
+    }
+
+    /// Returns the instrument number that is used at the given line,
+    /// which is the last instrument number set at or before the line.
+    /// Only the stored rows of the patterns are searched, see Pattern.
+    pub fn instr_at(&self, line: usize) -> u8 {
+        let (arr_end, last_idx) =
+            match self.line2pattern_pos(line) {
+                Some((arr_idx, row_idx)) => (arr_idx + 1, row_idx),
+                None                     => (self.arrangement.len(), usize::MAX),
+            };
+
+        for (arr_idx, pat_idx) in self.arrangement[..arr_end].iter().enumerate().rev() {
+            let last = if arr_idx + 1 == arr_end { last_idx } else { usize::MAX };
+            let instr =
+                self.patterns[*pat_idx].iter().rev()
+                    .filter(|(idx, _)| *idx <= last)
+                    .map(|(_, r)| r.instr)
+                    .find(|instr| *instr > 0);
+            if let Some(instr) = instr { return instr; }
+        }
+
+        0
+    }
+
+    /// Returns the output value `out_idx` (see output_count()) of this
+    /// track at the specified line depending on the kind of the track.
+    /// See also get_value().
+    pub fn get_output_value(&mut self, out_idx: usize, line: usize, fract_next_line: f64) -> f32 {
+        match self.kind {
+            TrackKind::Mixed => self.get_value(out_idx, line, fract_next_line),
+            TrackKind::Note  => self.pitch(line, fract_next_line).unwrap_or(0.0),
+            TrackKind::Automation { min, max } => {
+                let v = self.get_value(out_idx, line, fract_next_line);
+                if v < min      { min }
+                else if v > max { max }
+                else            { v }
+            },
+            TrackKind::Trigger => {
+                if self.line_skipped { return 0.0; }
+                match self.row_ref(line) {
+                    Some(r) => r.a as f32 / 255.0,
+                    None    => 0.0,
+                }
             },
         }
     }
+
+    /// Called for every line the track is not audible (muted or
+    /// not soloed). Returns a note off row once after the track went
+    /// silent, so that a still playing note can be stopped.
+    pub fn silence(&mut self) -> Option<Row> {
+        if self.silenced { return None; }
+        self.silenced = true;
+
+        let mut r = Row::new();
+        r.note = 1;
+        Some(r)
+    }
+
+    /// Remembers `line` as the last played line. Returns false if
+    /// it was already played.
+    pub fn advance_play_line(&mut self, line: usize) -> bool {
+        if self.played_line == line as i32 { return false; }
+        self.played_line = line as i32;
+        true
+    }
+
+    /// Returns the index of the pattern that is looped
+    /// in performance mode.
+    pub fn launched_pattern(&self) -> Option<usize> {
+        self.launched.map(|(pat_idx, _)| pat_idx)
+    }
+
+    /// Starts the queued launch at the song line `song_line`.
+    /// Returns the launched pattern (None for the arrangement)
+    /// if anything was launched.
+    pub fn launch_queued(&mut self, song_line: usize) -> Option<Option<usize>> {
+        let launch = self.queued.take()?;
+        self.launched =
+            match launch {
+                Launch::Pattern(pat_idx) => Some((pat_idx, song_line)),
+                Launch::Arrangement      => None,
+            };
+        self.desync();
+        Some(self.launched_pattern())
+    }
+
+    /// Sets the launched pattern without playing it and clears the
+    /// queue. Used by a frontend to display the launches of
+    /// the Tracker that actually plays.
+    pub fn set_launched_pattern(&mut self, pat_idx: Option<usize>) {
+        self.queued   = None;
+        self.launched = pat_idx.map(|p| (p, 0));
+    }
+
+    /// Maps the song line to the line of the track that is played.
+    /// If a pattern was launched, it is looped from the song line
+    /// it was launched at.
+    pub fn launch_line(&self, song_line: usize) -> usize {
+        let (pat_idx, at) =
+            match self.launched {
+                Some(l) => l,
+                None    => return song_line,
+            };
+        let arr_idx =
+            match self.arrangement.iter().position(|p| *p == pat_idx) {
+                Some(i) => i,
+                None    => return song_line,
+            };
+
+        let len = self.pattern_len(pat_idx);
+        if len == 0 { return song_line; }
+
+        self.arrangement_start_line(arr_idx) + (song_line + len - at % len) % len
+    }
+
+    /// Checks the condition of the row at `line`, see Condition::check().
+    /// If it does not pass, the note and the trigger of the line
+    /// are not played.
+    pub fn check_condition(&mut self, line: usize, pass: usize, rnd: f64) -> bool {
+        let ok =
+            match self.row_ref(line) {
+                Some(r) => r.cond.check(pass, rnd),
+                None    => true,
+            };
+        self.line_skipped = !ok;
+        ok
+    }
+
+    /// Marks the line as already sounded, the next time it is
+    /// played no note event is generated for it.
+    pub fn set_sounded_line(&mut self, line: usize) {
+        self.sounded_line = Some(line);
+    }
+
+    /// Returns true if the line was marked with set_sounded_line()
+    /// and clears the mark.
+    pub fn take_sounded_line(&mut self, line: usize) -> bool {
+        if self.sounded_line == Some(line) {
+            self.sounded_line = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets the last played line, so that the next line
+    /// is played in any case.
+    pub fn reset_play_line(&mut self) {
+        self.played_line  = -1;
+        self.line_skipped = false;
+        self.pitch.clear();
+    }
+
+    /// Called for every line the track is audible.
+    pub fn unsilence(&mut self) {
+        self.silenced = false;
+    }
+
+    /// Returns the interpolated value of the value column `col`
+    /// of this track at the specified line.
+    /// Only works if the interpolation was
+    /// initialized with self.sync_interpol_to_play_line() in self.play_line()!
+    pub fn get_value(&mut self, col: usize, line: usize, fract_next_line: f64) -> f32 {
+        let i =
+            match self.interpol.get_mut(col) {
+                Some(i) => i,
+                None    => return 0.0,
+            };
+
+        if line < i.line_a {
+            i.clear();
+        }
+
+        i.value(line, fract_next_line)
+    }
 }
@@ -1,4 +1,8 @@
 use crate::track::*;
+use crate::instrument::Instrument;
+use crate::groove::*;
+use crate::tuning::Tuning;
+use crate::scale::Scale;
 use crate::gui_painter::GUIPainter;
 
 /// This trait handles the output of a Tracker when being driven
@@ -15,6 +19,17 @@ pub trait OutputHandler {
     fn emit_play_line(&mut self, play_line: i32);
     /// Is used to output the song position in seconds.
     fn song_pos(&mut self) -> &mut f32;
+    /// Called by Tracker::tick() when a queued launch started on
+    /// a track. `pat_idx` is the looped pattern or None if the track
+    /// plays the arrangement again.
+    fn emit_launch(&mut self, _track_idx: usize, _pat_idx: Option<usize>) { }
+    /// Called by Tracker::record_note_at_play_pos() when a note
+    /// was written to a track.
+    fn emit_record(&mut self, _track_idx: usize, _line: usize, _note: u8) { }
+    /// Called by Tracker::tick() on every tick while the pitch of the
+    /// playing note of a track slides. `pitch` is a note number with
+    /// a fractional part.
+    fn emit_pitch(&mut self, _track_idx: usize, _pitch: f32) { }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -25,6 +40,8 @@ pub enum PlayHeadAction {
     Restart,
     NextLine,
     PrevLine,
+    /// Go to the line, without changing whether the song plays.
+    Seek(usize),
 }
 
 /// This trait provides an interface to synchronize the track data
@@ -37,22 +54,78 @@ pub enum PlayHeadAction {
 pub trait TrackerSync {
     /// Called by Tracker when a new Track is added.
     fn add_track(&mut self, t: Track);
+    /// Called by Tracker when a new Track is inserted at the given index.
+    fn insert_track(&mut self, track_idx: usize, t: Track);
+    /// Called by Tracker when a Track is removed.
+    fn remove_track(&mut self, track_idx: usize);
+    /// Called by Tracker when a Track is moved to a new index.
+    fn move_track(&mut self, from_idx: usize, to_idx: usize);
+    /// Called by Tracker when a Track is renamed.
+    fn rename_track(&mut self, track_idx: usize, name: String);
+    /// Called by Tracker when the kind of a Track is changed.
+    fn set_track_kind(&mut self, track_idx: usize, kind: TrackKind);
+    /// Called by Tracker when a Track is muted or unmuted.
+    fn set_mute(&mut self, track_idx: usize, mute: bool);
+    /// Called by Tracker when a Track is soloed or unsoloed.
+    fn set_solo(&mut self, track_idx: usize, solo: bool);
+    /// Called by Tracker when the freezing of the values of
+    /// not audible tracks is switched.
+    fn set_freeze_muted_values(&mut self, freeze: bool);
     /// Called by Tracker when the a note in a specific track and line is added.
     fn set_note(&mut self, track_idx: usize, line: usize, value: u8);
-    /// Called by Tracker when a value in a specific track and line
-    /// is added.
-    fn set_value(&mut self, track_idx: usize, line: usize, value: f32);
+    /// Called by Tracker when a value in a specific track, value column
+    /// and line is added.
+    fn set_value(&mut self, track_idx: usize, col: usize, line: usize, value: f32);
+    /// Called by Tracker when the instrument number in a specific
+    /// track and line is set.
+    fn set_instr(&mut self, track_idx: usize, line: usize, value: u8);
+    /// Called by Tracker when an instrument of the instrument table
+    /// is defined.
+    fn set_instrument(&mut self, num: u8, instr: Instrument);
+    /// Called by Tracker when the tuning of the notes is changed.
+    fn set_tuning(&mut self, tuning: Tuning);
+    /// Called by Tracker when a groove template is defined.
+    fn set_groove(&mut self, groove_idx: usize, groove: Groove);
+    /// Called by Tracker when the groove of the whole song is selected.
+    fn set_song_groove(&mut self, groove_idx: Option<usize>);
+    /// Called by Tracker when the groove of a track is selected.
+    fn set_track_groove(&mut self, track_idx: usize, groove_idx: Option<usize>);
     /// Called by Tracker when the a flag value in a specific track and line
     /// is added.
     fn set_a(&mut self, track_idx: usize, line: usize, value: u8);
     /// Called by Tracker when the b flag value in a specific track and line
     /// is added.
     fn set_b(&mut self, track_idx: usize, line: usize, value: u8);
+    /// Called by Tracker when a whole row in a specific track
+    /// and line is replaced.
+    fn set_row(&mut self, track_idx: usize, line: usize, row: Row);
+    /// Called by Tracker when a note should be played immediately
+    /// on a track, for auditioning notes while editing.
+    fn preview_note(&mut self, track_idx: usize, note: u8, instr: u8);
+    /// Called by Tracker when a note is played in record mode.
+    fn record_note(&mut self, track_idx: usize, note: u8, overdub: bool);
+    /// Called by Tracker when a pattern launch is queued for a track.
+    fn queue_launch(&mut self, track_idx: usize, launch: Option<Launch>);
+    /// Called by Tracker when the slide of a row is set.
+    fn set_slide(&mut self, track_idx: usize, line: usize, slide: Option<(u8, Interpolation)>);
+    /// Called by Tracker when the condition of a row is set.
+    fn set_cond(&mut self, track_idx: usize, line: usize, cond: Condition);
+    /// Called by Tracker when the seed of the random numbers
+    /// for the row conditions is changed.
+    fn set_seed(&mut self, seed: u64);
     /// Called by Tracker when an interpolation for a value should be set.
     /// Does nothing if no value at that position exists.
-    fn set_int(&mut self, track_idx: usize, line: usize, int: Interpolation);
+    fn set_int(&mut self, track_idx: usize, col: usize, line: usize, int: Interpolation);
+    /// Called by Tracker when the number of value columns of
+    /// a track is changed.
+    fn set_value_columns(&mut self, track_idx: usize, cols: usize);
     /// Called by Tracker when a value is removed from a track.
     fn remove_value(&mut self, track_idx: usize, line: usize);
+    /// Called by Tracker when the number of lines of a pattern is changed.
+    fn set_pattern_len(&mut self, track_idx: usize, pat_idx: usize, len: usize);
+    /// Called by Tracker when the lines per pattern of a track are changed
+    /// and the rows of the track are resampled.
+    fn resample_lpp(&mut self, track_idx: usize, lpp: usize);
     /// Called when the tracker should change the play head state:
     fn play_head(&mut self, _act: PlayHeadAction) { }
     /// Called when track data is loaded
@@ -65,12 +138,36 @@ pub struct TrackerNopSync { }
 
 impl TrackerSync for TrackerNopSync {
     fn add_track(&mut self, _t: Track) { }
-    fn set_value(&mut self, _track_idx: usize, _line: usize, _value: f32) { }
+    fn insert_track(&mut self, _track_idx: usize, _t: Track) { }
+    fn remove_track(&mut self, _track_idx: usize) { }
+    fn move_track(&mut self, _from_idx: usize, _to_idx: usize) { }
+    fn rename_track(&mut self, _track_idx: usize, _name: String) { }
+    fn set_track_kind(&mut self, _track_idx: usize, _kind: TrackKind) { }
+    fn set_mute(&mut self, _track_idx: usize, _mute: bool) { }
+    fn set_solo(&mut self, _track_idx: usize, _solo: bool) { }
+    fn set_freeze_muted_values(&mut self, _freeze: bool) { }
+    fn set_value(&mut self, _track_idx: usize, _col: usize, _line: usize, _value: f32) { }
     fn set_note(&mut self, _track_idx: usize, _line: usize, _value: u8) { }
+    fn set_instr(&mut self, _track_idx: usize, _line: usize, _value: u8) { }
+    fn set_instrument(&mut self, _num: u8, _instr: Instrument) { }
+    fn set_tuning(&mut self, _tuning: Tuning) { }
+    fn set_groove(&mut self, _groove_idx: usize, _groove: Groove) { }
+    fn set_song_groove(&mut self, _groove_idx: Option<usize>) { }
+    fn set_track_groove(&mut self, _track_idx: usize, _groove_idx: Option<usize>) { }
     fn set_a(&mut self, _track_idx: usize, _line: usize, _value: u8) { }
     fn set_b(&mut self, _track_idx: usize, _line: usize, _value: u8) { }
-    fn set_int(&mut self, _track_idx: usize, _line: usize, _int: Interpolation) { }
+    fn set_row(&mut self, _track_idx: usize, _line: usize, _row: Row) { }
+    fn preview_note(&mut self, _track_idx: usize, _note: u8, _instr: u8) { }
+    fn record_note(&mut self, _track_idx: usize, _note: u8, _overdub: bool) { }
+    fn queue_launch(&mut self, _track_idx: usize, _launch: Option<Launch>) { }
+    fn set_slide(&mut self, _track_idx: usize, _line: usize, _slide: Option<(u8, Interpolation)>) { }
+    fn set_cond(&mut self, _track_idx: usize, _line: usize, _cond: Condition) { }
+    fn set_seed(&mut self, _seed: u64) { }
+    fn set_int(&mut self, _track_idx: usize, _col: usize, _line: usize, _int: Interpolation) { }
+    fn set_value_columns(&mut self, _track_idx: usize, _cols: usize) { }
     fn remove_value(&mut self, _track_idx: usize, _line: usize) { }
+    fn set_pattern_len(&mut self, _track_idx: usize, _pat_idx: usize, _len: usize) { }
+    fn resample_lpp(&mut self, _track_idx: usize, _lpp: usize) { }
     fn deserialize_contents(&mut self, _track_idx: usize, _contents: TrackSerialized) { }
     fn play_head(&mut self, _act: PlayHeadAction) { }
 }
@@ -85,18 +182,42 @@ pub struct Tracker<SYNC> where SYNC: TrackerSync {
 pub lpb:            usize,
     /// ticks per row/line
 pub tpl:            usize,
-    /// number of lines per pattern
+    /// number of lines of new patterns of new tracks
 pub lpp:            usize,
     /// current play head, if -1 it will start with line 0
 pub play_line:      i32,
     /// The actual track data.
 pub tracks:         Vec<Track>,
+    /// The instrument table, instrument number 1 is at index 0.
+pub instruments:    Vec<Instrument>,
+    /// Maps the note numbers to frequencies.
+pub tuning:         Tuning,
+    /// The key and scale for entering notes in the editor.
+    /// Only used for editing, so it is not synchronized.
+pub scale:          Option<Scale>,
+    /// The groove templates, selected by their index.
+pub grooves:        Vec<Groove>,
+    /// The groove of all tracks that don't select their own groove.
+pub groove:         Option<usize>,
+    /// Seed of the random numbers for the row conditions.
+pub seed:           u64,
+    /// Number of passes through the song, starting at 0.
+pub pass:           usize,
     /// the synchronization class:
     sync:           SYNC,
     /// number of played ticks
     tick_count:     usize,
     /// interval between ticks in ms
 pub tick_interval:  usize,
+    /// If true, muted tracks keep their last value instead of
+    /// interpolating further.
+pub freeze_muted_values: bool,
+    /// Which registers of the value proxy are used by
+    /// a track, see assign_output_slots().
+    out_used:       Vec<bool>,
+    /// The line and the fraction of the next line of each track
+    /// in the current tick, kept to not allocate on every tick.
+    track_pos:      Vec<Option<(usize, f64)>>,
 }
 
 //impl<SYNC> DemOp for Tracker<SYNC> where SYNC: TrackerSync {
@@ -126,6 +247,22 @@ pub tick_interval:  usize,
 //    }
 //}
 
+/// Returns a random number from 0.0 to 1.0 that only depends on
+/// its arguments. This way the row conditions of a song play the
+/// same on every render with the same seed.
+fn cond_random(seed: u64, pass: usize, track_idx: usize, line: usize) -> f64 {
+    let mut x =
+          seed
+        ^ (pass      as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (track_idx as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (line      as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    // splitmix64 finalizer:
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
 impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
     pub fn new(sync: SYNC) -> Self {
         Tracker {
@@ -134,14 +271,25 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
             tick_interval:   5,
             lpp:            32,
             tracks:         Vec::new(),
+            instruments:    Vec::new(),
+            tuning:         Tuning::default(),
+            scale:          None,
+            grooves:        Vec::new(),
+            groove:         None,
+            seed:           0,
+            pass:           0,
             play_line:      -1,
             tick_count:     0,
+            freeze_muted_values: false,
+            out_used:       Vec::new(),
+            track_pos:      Vec::new(),
             sync,
         }
     }
 
     pub fn draw<P>(&self, p: &mut P, state: &mut GUIState) where P: GUIPainter {
-        let mut display_track_count = (p.get_area_size().0 / TRACK_WIDTH).floor() as usize;
+        let area_width = p.get_area_size().0;
+        let mut display_track_count = (area_width / TRACK_WIDTH).floor() as usize;
         if display_track_count < 1 {
             display_track_count = 1;
         }
@@ -155,20 +303,22 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
                 0
             };
 
+        let mut x = 0.0;
         for (i, t) in self.tracks.iter().enumerate().skip(skip_cnt) {
-            if display_track_count == 0 { break; }
-            display_track_count -= 1;
+            let width =
+                if i == 0 { t.width() + LINE_NUM_WIDTH }
+                else      { t.width() };
+            if x > 0.0 && (x + width) > area_width { break; }
 
             state.track_index = i;
             state.cursor_on_track = state.cursor_track_idx == i;
             state.lpb = self.lpb;
+            state.note_steps  = self.tuning.steps();
+            state.note_middle = self.tuning.middle_note;
             t.draw(p, state);
 
-            if i == 0 {
-                p.add_offs(FIRST_TRACK_WIDTH, 0.0);
-            } else {
-                p.add_offs(TRACK_WIDTH, 0.0);
-            }
+            p.add_offs(width, 0.0);
+            x += width;
         }
 
         p.set_offs(o);
@@ -183,6 +333,149 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
     pub fn add_track(&mut self, t: Track) {
         self.sync.add_track(t.clone());
         self.tracks.push(t);
+        self.assign_output_slots();
+    }
+
+    /// Inserts a track at `track_idx`, the following tracks
+    /// are moved one index up. If the index is beyond the last track
+    /// the track is appended.
+    pub fn insert_track(&mut self, track_idx: usize, t: Track) {
+        let track_idx =
+            if track_idx > self.tracks.len() { self.tracks.len() }
+            else { track_idx };
+        self.sync.insert_track(track_idx, t.clone());
+        self.tracks.insert(track_idx, t);
+        self.assign_output_slots();
+    }
+
+    pub fn remove_track(&mut self, track_idx: usize) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.remove_track(track_idx);
+        self.tracks.remove(track_idx);
+        self.assign_output_slots();
+    }
+
+    /// Moves the track at `from_idx` so that it ends up at
+    /// the index `to_idx`.
+    pub fn move_track(&mut self, from_idx: usize, to_idx: usize) {
+        if from_idx >= self.tracks.len() || to_idx >= self.tracks.len() {
+            return;
+        }
+        self.sync.move_track(from_idx, to_idx);
+        let t = self.tracks.remove(from_idx);
+        self.tracks.insert(to_idx, t);
+    }
+
+    pub fn rename_track(&mut self, track_idx: usize, name: &str) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.rename_track(track_idx, name.to_string());
+        self.tracks[track_idx].name = name.to_string();
+    }
+
+    /// Changes the kind of a track. The row data is kept, so
+    /// switching back to the previous kind is lossless.
+    pub fn set_track_kind(&mut self, track_idx: usize, kind: TrackKind) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_track_kind(track_idx, kind);
+        self.tracks[track_idx].kind = kind;
+        self.tracks[track_idx].desync();
+        self.assign_output_slots();
+    }
+
+    pub fn set_mute(&mut self, track_idx: usize, mute: bool) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_mute(track_idx, mute);
+        self.tracks[track_idx].mute = mute;
+    }
+
+    pub fn set_solo(&mut self, track_idx: usize, solo: bool) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_solo(track_idx, solo);
+        self.tracks[track_idx].solo = solo;
+    }
+
+    pub fn set_freeze_muted_values(&mut self, freeze: bool) {
+        self.sync.set_freeze_muted_values(freeze);
+        self.freeze_muted_values = freeze;
+    }
+
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.sync.set_tuning(tuning.clone());
+        self.tuning = tuning;
+    }
+
+    /// Defines the groove template at `groove_idx`, missing grooves
+    /// before it are filled with grooves without any delay.
+    pub fn set_groove(&mut self, groove_idx: usize, groove: Groove) {
+        self.sync.set_groove(groove_idx, groove.clone());
+
+        while self.grooves.len() <= groove_idx {
+            let name = format!("groove{}", self.grooves.len());
+            self.grooves.push(Groove::new(&name, vec![]));
+        }
+        self.grooves[groove_idx] = groove;
+    }
+
+    pub fn set_song_groove(&mut self, groove_idx: Option<usize>) {
+        self.sync.set_song_groove(groove_idx);
+        self.groove = groove_idx;
+    }
+
+    pub fn set_track_groove(&mut self, track_idx: usize, groove_idx: Option<usize>) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_track_groove(track_idx, groove_idx);
+        self.tracks[track_idx].groove = groove_idx;
+    }
+
+    /// Returns the groove that applies to the track, the groove
+    /// of the track takes precedence over the song groove.
+    fn track_groove(&self, track_idx: usize) -> Option<&Groove> {
+        let idx =
+            match self.tracks.get(track_idx).and_then(|t| t.groove) {
+                Some(idx) => Some(idx),
+                None      => self.groove,
+            };
+        idx.and_then(|i| self.grooves.get(i))
+    }
+
+    /// Number of lines of a bar, a bar has 4 beats.
+    pub fn bar_len(&self) -> usize {
+        if self.lpb < 1 { 4 } else { 4 * self.lpb }
+    }
+
+    /// Queues a launch for the track, which starts at the next bar
+    /// boundary while playing. A launched pattern is looped instead of
+    /// playing the arrangement. Only patterns used in the arrangement of
+    /// the track can be launched. None cancels the queued launch.
+    pub fn queue_launch(&mut self, track_idx: usize, launch: Option<Launch>) {
+        if track_idx >= self.tracks.len() { return; }
+        if let Some(Launch::Pattern(pat_idx)) = launch {
+            if !self.tracks[track_idx].arrangement.contains(&pat_idx) {
+                return;
+            }
+        }
+
+        self.sync.queue_launch(track_idx, launch);
+        self.tracks[track_idx].queued = launch;
+    }
+
+    /// Is called with the launches reported by
+    /// OutputHandler::emit_launch() of the Tracker that plays.
+    pub fn set_launched_pattern(&mut self, track_idx: usize, pat_idx: Option<usize>) {
+        if track_idx >= self.tracks.len() { return; }
+        self.tracks[track_idx].set_launched_pattern(pat_idx);
+    }
+
+    /// Returns a track name that is not used by any track yet.
+    pub fn new_track_name(&self) -> String {
+        let mut i = self.tracks.len();
+        loop {
+            let name = format!("trk{}", i);
+            if !self.tracks.iter().any(|t| t.name == name) {
+                return name;
+            }
+            i += 1;
+        }
     }
 
     pub fn max_line_count(&self) -> usize {
@@ -198,6 +491,7 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
     pub fn reset_pos(&mut self) {
         self.tick_count = 0;
         self.play_line  = -1;
+        self.pass       = 0;
         self.resync_tracks();
     }
 
@@ -230,43 +524,160 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
         self.handle_tick_count_change(output, values);
     }
 
+    pub fn tick_to_line<T>(&mut self, line: usize, output: &mut T, values: &std::rc::Rc<std::cell::RefCell<Vec<f32>>>)
+        where T: OutputHandler {
+
+        self.tick_count = line * self.tpl;
+
+        self.resync_tracks();
+        self.handle_tick_count_change(output, values);
+    }
+
     pub fn handle_tick_count_change<T>(&mut self, output: &mut T, values: &std::rc::Rc<std::cell::RefCell<Vec<f32>>>)
         where T: OutputHandler {
 
         let line_count = self.max_line_count();
 
         let mut new_play_line = self.tick_count / self.tpl;
-        let fract_ticks =
-            ((self.tick_count - (new_play_line * self.tpl)) as f64)
-            / self.tpl as f64;
 
         if new_play_line >= line_count {
             new_play_line = 0;
             self.tick_count = 1;
             self.play_line = -1;
+            self.pass += 1;
             self.resync_tracks();
         }
 
         if new_play_line as i32 != self.play_line {
             output.emit_play_line(new_play_line as i32);
+        }
+        //d// println!("TC: {} {}/{}", self.tick_count, new_play_line, self.play_line);
+
+        self.play_line = new_play_line as i32;
+
+        // Each track may be delayed by a groove, so the lines of the
+        // tracks start independently of the play line.
+        let pos = self.tick_count as f64 / self.tpl as f64;
+        let mut track_pos = std::mem::take(&mut self.track_pos);
+        track_pos.clear();
+        track_pos.extend(
+            (0..self.tracks.len())
+            .map(|i| grooved_line_pos(self.track_groove(i), pos)));
+
+        // A track is audible if it is not muted and either soloed or
+        // no track is soloed at all.
+        let any_solo = self.tracks.iter().any(|t| t.solo);
+        let (seed, pass) = (self.seed, self.pass);
+        let bar_len = self.bar_len();
+
+        for (track_idx, t) in self.tracks.iter_mut().enumerate() {
+            let line =
+                match track_pos[track_idx] {
+                    Some((line, _)) => line,
+                    None            => continue,
+                };
+            if !t.advance_play_line(line) { continue; }
+
+            if line % bar_len == 0 {
+                if let Some(pat_idx) = t.launch_queued(line) {
+                    output.emit_launch(track_idx, pat_idx);
+                }
+            }
+            let line = t.launch_line(line);
 
-            for (track_idx, t) in self.tracks.iter_mut().enumerate() {
-                let e = t.play_line(new_play_line);
-                if let Some(row) = e {
+            let play =
+                t.check_condition(
+                    line, pass, cond_random(seed, pass, track_idx, line));
+            let e = t.play_line(line).filter(|_| play);
+            let e = if t.take_sounded_line(line) { None } else { e };
+
+            let audible = !t.mute && (t.solo || !any_solo);
+            if !audible {
+                if let Some(row) = t.silence() {
                     output.emit_event(track_idx, &row);
                 }
+                continue;
             }
-        }
-        //d// println!("TC: {} {}/{}", self.tick_count, new_play_line, self.play_line);
+            t.unsilence();
 
-        self.play_line = new_play_line as i32;
+            if let Some(row) = e {
+                output.emit_event(track_idx, &row);
+            }
+        }
 
         *(output.song_pos()) = self.tick2song_pos_in_s();
 
+        // Each track has as many outputs as it has value columns
+        // (see Track::output_count()), which go to the registers assigned
+        // to the track. Registers without a track (eg. after a track was
+        // removed) are reset, so that no stale values remain.
+        // The pulses of triggers are their notes, so trigger tracks that
+        // are not audible output 0.0, even if the values are frozen.
+        let freeze = self.freeze_muted_values;
+
         let mut v = values.borrow_mut();
-        for (idx, t) in self.tracks.iter_mut().enumerate() {
-            if idx >= v.len() { break; }
-            v[idx] = t.get_value(new_play_line, fract_ticks);
+        for (track_idx, t) in self.tracks.iter_mut().enumerate() {
+            let audible = !t.mute && (t.solo || !any_solo);
+            let (line, fract) = track_pos[track_idx].unwrap_or((0, 0.0));
+            let line = t.launch_line(line);
+
+            if audible && t.is_sliding(line) {
+                if let Some(pitch) = t.pitch(line, fract) {
+                    output.emit_pitch(track_idx, pitch);
+                }
+            }
+
+            for col in 0..t.output_count() {
+                let slot =
+                    match t.out_slots.get(col) {
+                        Some(slot) if *slot < v.len() => *slot,
+                        _ => continue,
+                    };
+                if !audible && t.kind == TrackKind::Trigger {
+                    v[slot] = 0.0;
+                } else if audible || !freeze {
+                    v[slot] = t.get_output_value(col, line, fract);
+                }
+            }
+        }
+
+        for (slot, val) in v.iter_mut().enumerate() {
+            if !self.out_used.get(slot).copied().unwrap_or(false) {
+                *val = 0.0;
+            }
+        }
+
+        self.track_pos = track_pos;
+    }
+
+    /// Assigns the outputs of the tracks to the registers of the value
+    /// proxy. A track keeps its registers when other tracks are inserted,
+    /// removed or moved, so the signal graph keeps reading the same track.
+    /// New outputs get the lowest free registers, so the outputs of
+    /// tracks that were added in order follow the track order.
+    /// Called whenever tracks or their outputs change.
+    pub fn assign_output_slots(&mut self) {
+        let used = &mut self.out_used;
+        used.clear();
+        for t in self.tracks.iter_mut() {
+            let count = t.output_count();
+            t.out_slots.truncate(count);
+            for slot in t.out_slots.iter() {
+                if *slot >= used.len() { used.resize(*slot + 1, false); }
+                used[*slot] = true;
+            }
+        }
+
+        for t in self.tracks.iter_mut() {
+            while t.out_slots.len() < t.output_count() {
+                let slot =
+                    match used.iter().position(|u| !*u) {
+                        Some(slot) => slot,
+                        None       => { used.push(false); used.len() - 1 },
+                    };
+                used[slot] = true;
+                t.out_slots.push(slot);
+            }
         }
     }
 
@@ -277,56 +688,225 @@ impl<SYNC> Tracker<SYNC> where SYNC: TrackerSync {
         self.handle_tick_count_change(output, values);
     }
 
-    pub fn set_int(&mut self, track_idx: usize, line: usize, int: Interpolation) {
-        self.sync.set_int(track_idx, line, int);
-        self.tracks[track_idx].set_int(line, int);
+    pub fn set_int(&mut self, track_idx: usize, col: usize, line: usize, int: Interpolation) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_int(track_idx, col, line, int);
+        self.tracks[track_idx].set_int(col, line, int);
     }
 
     pub fn set_note(&mut self, track_idx: usize, line: usize, v: u8) {
+        if track_idx >= self.tracks.len() { return; }
         self.sync.set_note(track_idx, line, v);
         self.tracks[track_idx].set_note(line, v);
     }
 
+    /// Plays the note of the row at `line` of the track immediately,
+    /// with the instrument that is used at that line. Rows without
+    /// a note play nothing.
+    pub fn preview_line(&mut self, track_idx: usize, line: usize) {
+        let (note, instr) =
+            match self.tracks.get(track_idx) {
+                Some(t) if t.kind.has_notes() => {
+                    match t.row_checked(line) {
+                        Some((_, row)) => (row.note, t.instr_at(line)),
+                        None           => return,
+                    }
+                },
+                _ => return,
+            };
+        if note <= 1 { return; }
+
+        self.sync.preview_note(track_idx, note, instr);
+    }
+
+    /// Records a note that is played live on a track. The note is
+    /// written by the Tracker that plays, see record_note_at_play_pos().
+    pub fn record_note(&mut self, track_idx: usize, note: u8, overdub: bool) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.record_note(track_idx, note, overdub);
+    }
+
+    /// Writes the note to the line nearest to the play position and
+    /// plays it immediately. With `overdub` a note that is already in
+    /// that line is kept, otherwise it is replaced.
+    /// The written note is reported with OutputHandler::emit_record().
+    pub fn record_note_at_play_pos<T>(&mut self, output: &mut T, track_idx: usize, note: u8, overdub: bool)
+        where T: OutputHandler {
+
+        if track_idx >= self.tracks.len() { return; }
+        if !self.tracks[track_idx].kind.has_notes() { return; }
+
+        let line_count = self.max_line_count();
+        if line_count == 0 { return; }
+
+        // The line is rounded with the line position of the track, which
+        // may be delayed by a groove, like the lines are played.
+        let pos = self.tick_count as f64 / self.tpl as f64;
+        let (line, rounded_up) =
+            match grooved_line_pos(self.track_groove(track_idx), pos) {
+                Some((line, fract)) if fract >= 0.5 => (line + 1, true),
+                Some((line, _))                     => (line, false),
+                None                                => (0, true),
+            };
+        let line = if line >= line_count { 0 } else { line };
+
+        let t    = &mut self.tracks[track_idx];
+        let line = t.launch_line(line);
+        if line >= t.line_count() { return; }
+
+        let has_note = t.row_checked(line).map(|(_, r)| r.note > 0).unwrap_or(false);
+        if !(overdub && has_note) {
+            t.set_note(line, note);
+            output.emit_record(track_idx, line, note);
+        }
+
+        // The note is sounded now, so it must not be played again
+        // if the line was rounded up to the next line, which may be
+        // the first line after the end of the song.
+        if rounded_up {
+            t.set_sounded_line(line);
+        }
+
+        let mut row = Row::new();
+        row.note  = note;
+        row.instr = t.instr_at(line);
+        output.emit_event(track_idx, &row);
+    }
+
+    /// Writes a note that was recorded by the Tracker that plays
+    /// (see OutputHandler::emit_record()), without synchronizing it back.
+    pub fn set_recorded_note(&mut self, track_idx: usize, line: usize, note: u8) {
+        if track_idx >= self.tracks.len() { return; }
+        self.tracks[track_idx].set_note(line, note);
+    }
+
+    pub fn set_instr(&mut self, track_idx: usize, line: usize, v: u8) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_instr(track_idx, line, v);
+        self.tracks[track_idx].set_instr(line, v);
+    }
+
+    /// Defines the instrument with the number `num` (starting at 1).
+    /// Missing instruments before it are filled with empty instruments.
+    pub fn set_instrument(&mut self, num: u8, instr: Instrument) {
+        if num == 0 { return; }
+        self.sync.set_instrument(num, instr.clone());
+
+        let idx = (num - 1) as usize;
+        while self.instruments.len() <= idx {
+            let name = format!("instr{}", self.instruments.len() + 1);
+            self.instruments.push(Instrument::new(&name));
+        }
+        self.instruments[idx] = instr;
+    }
+
+    /// Returns the instrument with the number `num`, or None if it
+    /// is not defined.
+    pub fn instrument(&self, num: u8) -> Option<&Instrument> {
+        if num == 0 { return None; }
+        self.instruments.get((num - 1) as usize)
+    }
+
     pub fn set_a(&mut self, track_idx: usize, line: usize, v: u8) {
+        if track_idx >= self.tracks.len() { return; }
         self.sync.set_a(track_idx, line, v);
         self.tracks[track_idx].set_a(line, v);
     }
 
     pub fn set_b(&mut self, track_idx: usize, line: usize, v: u8) {
+        if track_idx >= self.tracks.len() { return; }
         self.sync.set_b(track_idx, line, v);
         self.tracks[track_idx].set_b(line, v);
     }
 
-    pub fn set_value(&mut self, track_idx: usize, line: usize, value: f32) {
-        self.sync.set_value(track_idx, line, value);
-        self.tracks[track_idx].set_value(line, value);
+    /// Replaces the row at `line` of the track with one message,
+    /// instead of setting each column on its own.
+    pub fn set_row(&mut self, track_idx: usize, line: usize, row: Row) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_row(track_idx, line, row.clone());
+        self.tracks[track_idx].set_row(line, row);
+    }
+
+    pub fn set_slide(&mut self, track_idx: usize, line: usize, slide: Option<(u8, Interpolation)>) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_slide(track_idx, line, slide);
+        self.tracks[track_idx].set_slide(line, slide);
+    }
+
+    pub fn set_cond(&mut self, track_idx: usize, line: usize, cond: Condition) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_cond(track_idx, line, cond);
+        self.tracks[track_idx].set_cond(line, cond);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.sync.set_seed(seed);
+        self.seed = seed;
+    }
+
+    pub fn set_value(&mut self, track_idx: usize, col: usize, line: usize, value: f32) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_value(track_idx, col, line, value);
+        self.tracks[track_idx].set_value(col, line, value);
+    }
+
+    pub fn set_value_columns(&mut self, track_idx: usize, cols: usize) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_value_columns(track_idx, cols);
+        self.tracks[track_idx].set_value_columns(cols);
+        self.assign_output_slots();
     }
 
     fn resync_tracks(&mut self) {
-        for t in self.tracks.iter_mut() { t.desync(); }
+        for t in self.tracks.iter_mut() {
+            t.desync();
+            t.reset_play_line();
+        }
     }
 
     pub fn remove_value(&mut self, track_idx: usize, line: usize) {
+        if track_idx >= self.tracks.len() { return; }
         self.sync.remove_value(track_idx, line);
         self.tracks[track_idx].remove_value(line);
     }
 
+    pub fn set_pattern_len(&mut self, track_idx: usize, pat_idx: usize, len: usize) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.set_pattern_len(track_idx, pat_idx, len);
+        self.tracks[track_idx].set_pattern_len(pat_idx, len);
+    }
+
+    pub fn resample_lpp(&mut self, track_idx: usize, lpp: usize) {
+        if track_idx >= self.tracks.len() { return; }
+        self.sync.resample_lpp(track_idx, lpp);
+        self.tracks[track_idx].resample_lpp(lpp);
+    }
+
     pub fn serialize_tracks(&self) -> Vec<TrackSerialized> {
         self.tracks.iter().map(|t| t.serialize_contents()).collect()
     }
 
     pub fn deserialize_contents(&mut self, track_idx: usize, contents: TrackSerialized) {
+        if track_idx >= self.tracks.len() { return; }
         self.sync.deserialize_contents(track_idx, contents.clone());
         println!("DESER {}", track_idx);
         self.tracks[track_idx].deserialize_contents(&contents);
+        self.assign_output_slots();
     }
 
+    /// Replaces all tracks with the tracks of a project file,
+    /// in the order, with the names and kinds stored there.
     pub fn deserialize_tracks(&mut self, tracks: Vec<TrackSerialized>) {
+        while !self.tracks.is_empty() {
+            let last_idx = self.tracks.len() - 1;
+            self.remove_track(last_idx);
+        }
+
         for ts in tracks.iter() {
-            if let Some((i, _)) =
-                self.tracks.iter_mut().enumerate().find(|(_, t)| t.name == ts.name) {
-                self.deserialize_contents(i, ts.clone());
-            }
+            let mut t = Track::new_kind(&ts.name, self.lpp, ts.kind);
+            t.deserialize_contents(ts);
+            let track_idx = self.tracks.len();
+            self.insert_track(track_idx, t);
         }
     }
 
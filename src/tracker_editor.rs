@@ -1,6 +1,8 @@
 use crate::tracker::*;
 use crate::track::*;
 use crate::gui_painter::*;
+use crate::scale::{Scale, ScaleKind};
+use crate::mpt_clipboard::{rows_to_mpt, mpt_to_rows};
 use std::rc::Rc;
 use std::cell::RefCell;
 
@@ -8,9 +10,31 @@ pub struct TrackerEditor<SYNC> where SYNC: TrackerSync {
     pub tracker:    Rc<RefCell<Tracker<SYNC>>>,
     cur_track_idx:  usize,
     cur_line_idx:   usize,
+    cur_col_idx:    usize,
     scroll_offs:    usize,
     redraw_flag:    bool,
     step_size:      usize,
+    /// If not RecordMode::Off, entered notes are recorded at
+    /// the play position instead of the cursor.
+    pub record:     RecordMode,
+    /// If true, the notes the cursor moves over are played.
+    pub audition_on_move: bool,
+    /// If true, entered notes are moved to the nearest note
+    /// of the scale of the Tracker.
+    pub snap_to_scale: bool,
+    /// The track and line of the block mark, the block
+    /// reaches from there to the cursor.
+    block_mark:     Option<(usize, usize)>,
+}
+
+/// How notes are recorded while playing, see Tracker::record_note().
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RecordMode {
+    Off,
+    /// Notes already in the track are replaced.
+    Replace,
+    /// Notes already in the track are kept.
+    Overdub,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -18,19 +42,49 @@ pub enum TrackerInput {
     Delete,
     SetNote(u8),
     SetValue(f32),
+    SetInstr(u8),
     SetA(u8),
     SetB(u8),
+    SetCondition(Condition),
+    SetSlide(Option<(u8, Interpolation)>),
     SetInterpStep,
     SetInterpLerp,
     SetInterpSStep,
     SetInterpExp,
     SetStep(usize),
+    SetPatternLen(usize),
+    ResampleTrackLpp(usize),
+    InsertTrack(TrackKind),
+    SetTrackKind(TrackKind),
+    DeleteTrack,
+    MoveTrackLeft,
+    MoveTrackRight,
+    ToggleMute,
+    ToggleSolo,
+    ToggleFreezeMutedValues,
+    SetTrackGroove(Option<usize>),
+    QueueCursorPattern,
+    QueueLaunch(Option<Launch>),
+    SetSongGroove(Option<usize>),
+    AddValueColumn,
+    RemoveValueColumn,
+    ValueColumnLeft,
+    ValueColumnRight,
     StepDown,
     StepUp,
     RowDown,
     RowUp,
     TrackLeft,
     TrackRight,
+    ToggleRecord,
+    ToggleAuditionOnMove,
+    ToggleSnapToScale,
+    /// Stamps a chord with the given number of notes, see StampChord
+    /// in process_input().
+    StampChord(usize),
+    /// Sets the block mark at the cursor, or removes it if
+    /// it is already set.
+    ToggleBlockMark,
     PlayHead(PlayHeadAction),
 }
 
@@ -40,9 +94,14 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
             tracker,
             cur_track_idx:      0,
             cur_line_idx:       0,
+            cur_col_idx:        0,
             scroll_offs:        0,
             redraw_flag:        true,
             step_size:          1,
+            record:             RecordMode::Off,
+            audition_on_move:   false,
+            snap_to_scale:      false,
+            block_mark:         None,
         }
     }
 
@@ -64,14 +123,23 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
         let mut gs = GUIState {
             cursor_track_idx: self.cur_track_idx,
             track_index:      0,
+            track_kind:       TrackKind::Mixed,
+            track_width:      TRACK_WIDTH,
+            value_columns:    1,
+            cursor_value_col: self.cur_col_idx,
             cursor_on_track:  false,
             cursor_on_line:   false,
             scroll_offs:      self.scroll_offs,
             play_on_line:     false,
             pattern_index:    0,
+            pattern_start:    false,
             on_beat:          false,
             cursor_line:      self.cur_line_idx,
             lpb:              0,
+            note_steps:       12,
+            note_middle:      60,
+            scale:            self.tracker.borrow().scale,
+            block:            self.block_mark.map(|_| self.block()),
             play_line,
         };
         self.tracker.borrow_mut().draw(p, &mut gs);
@@ -80,23 +148,215 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
         self.scroll_offs   = gs.scroll_offs;
     }
 
+    pub fn rename_current_track(&mut self, name: &str) {
+        self.redraw_flag = true;
+        self.tracker.borrow_mut().rename_track(self.cur_track_idx, name);
+    }
+
+    /// Returns the first and last track and the first and last line
+    /// of the block between the block mark and the cursor. Without
+    /// a block mark the block is the cursor row.
+    fn block(&self) -> (usize, usize, usize, usize) {
+        let (mark_track, mark_line) =
+            self.block_mark.unwrap_or((self.cur_track_idx, self.cur_line_idx));
+
+        (mark_track.min(self.cur_track_idx),
+         mark_track.max(self.cur_track_idx),
+         mark_line.min(self.cur_line_idx),
+         mark_line.max(self.cur_line_idx))
+    }
+
+    pub fn block_marked(&self) -> bool { self.block_mark.is_some() }
+
+    /// Returns the rows of the block in the text format of the
+    /// OpenMPT/ModPlug clipboard, see mpt_clipboard::rows_to_mpt().
+    pub fn copy_block(&self) -> String {
+        let (t0, t1, l0, l1) = self.block();
+        let tracker = self.tracker.borrow();
+
+        let channels : Vec<Vec<Row>> =
+            tracker.tracks.iter()
+                .skip(t0)
+                .take(t1 + 1 - t0)
+                .map(|t| {
+                    (l0..=l1).map(|line| {
+                        t.row_checked(line)
+                         .map(|(_, r)| r)
+                         .unwrap_or_else(Row::new)
+                    }).collect()
+                })
+                .collect();
+
+        rows_to_mpt(&channels)
+    }
+
+    /// Writes the rows of text in the OpenMPT/ModPlug clipboard format
+    /// to the tracks at the cursor, the first channel goes to the cursor
+    /// track and the next channels to the tracks right of it. Rows after
+    /// the end of a track are cut off, the values of the rows are kept.
+    /// Returns the number of pasted channels.
+    pub fn paste_block(&mut self, text: &str) -> Result<usize, String> {
+        let channels = mpt_to_rows(text)?;
+        self.redraw_flag = true;
+
+        let mut tracker = self.tracker.borrow_mut();
+        let mut count   = 0;
+        for (i, rows) in channels.iter().enumerate() {
+            let track_idx = self.cur_track_idx + i;
+            if track_idx >= tracker.tracks.len() { break; }
+            let has_notes = tracker.tracks[track_idx].kind.has_notes();
+
+            for (j, row) in rows.iter().enumerate() {
+                let line = self.cur_line_idx + j;
+                let mut new_row =
+                    match tracker.tracks[track_idx].row_checked(line) {
+                        Some((_, r)) => r,
+                        None         => break,
+                    };
+                if has_notes {
+                    new_row.note  = row.note;
+                    new_row.instr = row.instr;
+                }
+                new_row.a = row.a;
+                new_row.b = row.b;
+                tracker.set_row(track_idx, line, new_row);
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     pub fn process_input(&mut self, input: TrackerInput) {
         self.redraw_flag = true;
 
+        let kind =
+            self.tracker.borrow().tracks
+                .get(self.cur_track_idx)
+                .map(|t| t.kind)
+                .unwrap_or(TrackKind::Mixed);
+
+        let scale = self.tracker.borrow().scale;
+        let input =
+            match (input, scale) {
+                (TrackerInput::SetNote(v), Some(scale)) if self.snap_to_scale =>
+                    TrackerInput::SetNote(scale.snap(v)),
+                (input, _) => input,
+            };
+
         match input {
+            TrackerInput::SetNote(v) if self.record != RecordMode::Off => {
+                if kind.has_notes() {
+                    self.tracker.borrow_mut()
+                        .record_note(
+                            self.cur_track_idx,
+                            v,
+                            self.record == RecordMode::Overdub);
+                }
+            },
+            TrackerInput::ToggleSnapToScale => {
+                self.snap_to_scale = !self.snap_to_scale;
+            },
+            // Builds a chord in the scale of the Tracker on the note
+            // under the cursor. The notes are written to the cursor
+            // track and the tracks with notes right of it.
+            // Without a scale the chord is built in the major scale
+            // of the note.
+            TrackerInput::StampChord(size) => {
+                let mut tracker = self.tracker.borrow_mut();
+                let root =
+                    tracker.tracks.get(self.cur_track_idx)
+                        .and_then(|t| t.row_checked(self.cur_line_idx))
+                        .map(|(_, r)| r.note)
+                        .unwrap_or(0);
+
+                if root > 1 {
+                    let scale =
+                        scale.unwrap_or(Scale::new(root % 12, ScaleKind::Major));
+
+                    let mut track_idx = self.cur_track_idx;
+                    for note in scale.chord(root, size) {
+                        while track_idx < tracker.tracks.len()
+                              && !tracker.tracks[track_idx].kind.has_notes() {
+                            track_idx += 1;
+                        }
+                        if track_idx >= tracker.tracks.len() { break; }
+
+                        tracker.set_note(track_idx, self.cur_line_idx, note);
+                        track_idx += 1;
+                    }
+                }
+            },
+            TrackerInput::ToggleBlockMark => {
+                self.block_mark =
+                    match self.block_mark {
+                        Some(_) => None,
+                        None    => Some((self.cur_track_idx, self.cur_line_idx)),
+                    };
+            },
+            TrackerInput::ToggleAuditionOnMove => {
+                self.audition_on_move = !self.audition_on_move;
+            },
+            TrackerInput::ToggleRecord => {
+                self.record =
+                    match self.record {
+                        RecordMode::Off     => RecordMode::Replace,
+                        RecordMode::Replace => RecordMode::Overdub,
+                        RecordMode::Overdub => RecordMode::Off,
+                    };
+            },
             TrackerInput::SetNote(v) => {
+                match kind {
+                    TrackKind::Trigger => {
+                        self.tracker.borrow_mut()
+                            .set_a(
+                                self.cur_track_idx,
+                                self.cur_line_idx,
+                                if v == 1 { 0 } else { 0xFF });
+                    },
+                    _ if kind.has_notes() => {
+                        let mut tracker = self.tracker.borrow_mut();
+                        tracker.set_note(
+                            self.cur_track_idx,
+                            self.cur_line_idx,
+                            v);
+                        tracker.preview_line(
+                            self.cur_track_idx, self.cur_line_idx);
+                    },
+                    _ => (),
+                }
+            },
+            TrackerInput::SetInstr(v) => {
+                if kind.has_notes() {
+                    self.tracker.borrow_mut()
+                        .set_instr(
+                            self.cur_track_idx,
+                            self.cur_line_idx,
+                            v);
+                }
+            },
+            TrackerInput::SetA(v) => {
                 self.tracker.borrow_mut()
-                    .set_note(
+                    .set_a(
                         self.cur_track_idx,
                         self.cur_line_idx,
                         v);
             },
-            TrackerInput::SetA(v) => {
+            TrackerInput::SetCondition(c) => {
                 self.tracker.borrow_mut()
-                    .set_a(
+                    .set_cond(
                         self.cur_track_idx,
                         self.cur_line_idx,
-                        v);
+                        c);
+            },
+            TrackerInput::SetSlide(slide) => {
+                if kind.has_notes() {
+                    self.tracker.borrow_mut()
+                        .set_slide(
+                            self.cur_track_idx,
+                            self.cur_line_idx,
+                            slide);
+                }
             },
             TrackerInput::SetB(v) => {
                 self.tracker.borrow_mut()
@@ -106,11 +366,14 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
                         v);
             },
             TrackerInput::SetValue(v) => {
-                self.tracker.borrow_mut()
-                    .set_value(
-                        self.cur_track_idx,
-                        self.cur_line_idx,
-                        v);
+                if kind.has_values() {
+                    self.tracker.borrow_mut()
+                        .set_value(
+                            self.cur_track_idx,
+                            self.cur_col_idx,
+                            self.cur_line_idx,
+                            v);
+                }
             },
             TrackerInput::Delete => {
                 self.tracker.borrow_mut()
@@ -153,6 +416,7 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
                 self.tracker.borrow_mut()
                     .set_int(
                         self.cur_track_idx,
+                        self.cur_col_idx,
                         self.cur_line_idx,
                         Interpolation::Step);
             },
@@ -160,6 +424,7 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
                 self.tracker.borrow_mut()
                     .set_int(
                         self.cur_track_idx,
+                        self.cur_col_idx,
                         self.cur_line_idx,
                         Interpolation::Lerp);
             },
@@ -167,6 +432,7 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
                 self.tracker.borrow_mut()
                     .set_int(
                         self.cur_track_idx,
+                        self.cur_col_idx,
                         self.cur_line_idx,
                         Interpolation::SStep);
             },
@@ -174,12 +440,133 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
                 self.tracker.borrow_mut()
                     .set_int(
                         self.cur_track_idx,
+                        self.cur_col_idx,
                         self.cur_line_idx,
                         Interpolation::Exp);
             },
             TrackerInput::SetStep(s) => {
                 self.step_size = s;
             },
+            TrackerInput::InsertTrack(kind) => {
+                let mut tracker = self.tracker.borrow_mut();
+                let name = tracker.new_track_name();
+                let lpp  = tracker.lpp;
+                let idx  =
+                    if tracker.tracks.is_empty() { 0 }
+                    else { self.cur_track_idx + 1 };
+                tracker.insert_track(idx, Track::new_kind(&name, lpp, kind));
+                self.cur_track_idx = idx;
+            },
+            TrackerInput::SetTrackKind(kind) => {
+                self.tracker.borrow_mut().set_track_kind(self.cur_track_idx, kind);
+            },
+            TrackerInput::DeleteTrack => {
+                self.tracker.borrow_mut().remove_track(self.cur_track_idx);
+            },
+            TrackerInput::MoveTrackLeft => {
+                if self.cur_track_idx > 0 {
+                    self.tracker.borrow_mut().move_track(
+                        self.cur_track_idx, self.cur_track_idx - 1);
+                    self.cur_track_idx -= 1;
+                }
+            },
+            TrackerInput::MoveTrackRight => {
+                if self.cur_track_idx + 1 < self.tracker.borrow().tracks.len() {
+                    self.tracker.borrow_mut().move_track(
+                        self.cur_track_idx, self.cur_track_idx + 1);
+                    self.cur_track_idx += 1;
+                }
+            },
+            TrackerInput::ToggleMute => {
+                let mut tracker = self.tracker.borrow_mut();
+                if self.cur_track_idx < tracker.tracks.len() {
+                    let mute = !tracker.tracks[self.cur_track_idx].mute;
+                    tracker.set_mute(self.cur_track_idx, mute);
+                }
+            },
+            TrackerInput::ToggleSolo => {
+                let mut tracker = self.tracker.borrow_mut();
+                if self.cur_track_idx < tracker.tracks.len() {
+                    let solo = !tracker.tracks[self.cur_track_idx].solo;
+                    tracker.set_solo(self.cur_track_idx, solo);
+                }
+            },
+            TrackerInput::ToggleFreezeMutedValues => {
+                let mut tracker = self.tracker.borrow_mut();
+                let freeze = !tracker.freeze_muted_values;
+                tracker.set_freeze_muted_values(freeze);
+            },
+            TrackerInput::SetTrackGroove(groove_idx) => {
+                self.tracker.borrow_mut()
+                    .set_track_groove(self.cur_track_idx, groove_idx);
+            },
+            TrackerInput::QueueCursorPattern => {
+                let mut tracker = self.tracker.borrow_mut();
+                if let Some((pat_idx, _)) =
+                    tracker.tracks.get(self.cur_track_idx)
+                        .and_then(|t| t.row_checked(self.cur_line_idx)) {
+
+                    tracker.queue_launch(
+                        self.cur_track_idx, Some(Launch::Pattern(pat_idx)));
+                }
+            },
+            TrackerInput::QueueLaunch(launch) => {
+                self.tracker.borrow_mut()
+                    .queue_launch(self.cur_track_idx, launch);
+            },
+            TrackerInput::SetSongGroove(groove_idx) => {
+                self.tracker.borrow_mut().set_song_groove(groove_idx);
+            },
+            TrackerInput::AddValueColumn => {
+                let mut tracker = self.tracker.borrow_mut();
+                if let Some(cols) =
+                    tracker.tracks.get(self.cur_track_idx).map(|t| t.value_columns) {
+
+                    tracker.set_value_columns(self.cur_track_idx, cols + 1);
+                }
+            },
+            TrackerInput::RemoveValueColumn => {
+                let mut tracker = self.tracker.borrow_mut();
+                if let Some(cols) =
+                    tracker.tracks.get(self.cur_track_idx).map(|t| t.value_columns) {
+
+                    if cols > 1 {
+                        tracker.set_value_columns(self.cur_track_idx, cols - 1);
+                    }
+                }
+            },
+            TrackerInput::ValueColumnLeft => {
+                if self.cur_col_idx > 0 {
+                    self.cur_col_idx -= 1;
+                }
+            },
+            TrackerInput::ValueColumnRight => {
+                self.cur_col_idx += 1;
+            },
+            TrackerInput::ResampleTrackLpp(lpp) => {
+                let mut tracker = self.tracker.borrow_mut();
+                if self.cur_track_idx < tracker.tracks.len() {
+                    let old_lpp = tracker.tracks[self.cur_track_idx].lpp;
+                    tracker.resample_lpp(self.cur_track_idx, lpp);
+                    if old_lpp > 0 {
+                        self.cur_line_idx = (self.cur_line_idx * lpp) / old_lpp;
+                    }
+                }
+            },
+            TrackerInput::SetPatternLen(len) => {
+                let mut tracker = self.tracker.borrow_mut();
+                if self.cur_track_idx < tracker.tracks.len() {
+                    let pat_idx =
+                        tracker.tracks[self.cur_track_idx]
+                            .row_checked(self.cur_line_idx)
+                            .map(|(pat_idx, _)| pat_idx);
+
+                    if let Some(pat_idx) = pat_idx {
+                        tracker.set_pattern_len(
+                            self.cur_track_idx, pat_idx, len);
+                    }
+                }
+            },
         };
 
         if self.tracker.borrow().tracks.len() == 0 {
@@ -190,9 +577,26 @@ impl<SYNC> TrackerEditor<SYNC> where SYNC: TrackerSync {
             self.cur_track_idx = self.tracker.borrow().tracks.len() - 1;
         }
 
+        let value_columns =
+            self.tracker.borrow().tracks[self.cur_track_idx].value_columns;
+        if self.cur_col_idx >= value_columns {
+            self.cur_col_idx = value_columns - 1;
+        }
+
         if self.cur_line_idx >= self.tracker.borrow().max_line_count() {
             self.cur_line_idx = self.tracker.borrow().max_line_count();
             if self.cur_line_idx > 0 { self.cur_line_idx -= 1; }
         }
+
+        if self.audition_on_move {
+            match input {
+                TrackerInput::StepDown | TrackerInput::StepUp
+                | TrackerInput::RowDown | TrackerInput::RowUp => {
+                    self.tracker.borrow_mut()
+                        .preview_line(self.cur_track_idx, self.cur_line_idx);
+                },
+                _ => (),
+            }
+        }
     }
 }
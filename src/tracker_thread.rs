@@ -1,11 +1,15 @@
 use crate::tracker::*;
-use crate::track::{Track, Row, Interpolation, TrackSerialized};
+use crate::track::{Track, TrackKind, Row, Interpolation, Condition, Launch, TrackSerialized};
+use crate::instrument::Instrument;
+use crate::groove::Groove;
+use crate::tuning::Tuning;
 use crate::vval_opin::vv2opin;
 use wave_sickle::new_slaughter;
 use crate::audio_dev_thread::start_audio_thread;
 use wctr_signal_ops::*;
 use crate::scopes::{Scopes, SCOPE_SAMPLES, SCOPE_WIDTH};
 use crate::audio::AudioFrontend;
+use crate::sync_server::{SyncServer, SyncCommand, output_names};
 
 pub struct TrackerThreadOutput {
     pub pos:                    i32,
@@ -13,8 +17,18 @@ pub struct TrackerThreadOutput {
     pub cpu:                    (f64, f64, f64),
     pub audio_scope_samples:    Vec<Vec<f32>>,
     pub audio_scope_done:       bool,
-        track_notes:            Vec<u8>,
-        events:                 Vec<(usize, u8, u8)>,
+        /// The playing (note, instrument) per track.
+        track_notes:            Vec<(u8, u8)>,
+        /// (track index, note or 1 for note off, note, instrument)
+        events:                 Vec<(usize, u8, u8, u8)>,
+    /// The pattern launches (track index, launched pattern) that
+    /// were not yet shown by the frontend.
+    pub launches:               Vec<(usize, Option<usize>)>,
+    /// The recorded notes (track index, line, note) that were
+    /// not yet written to the tracks of the frontend.
+    pub recorded:               Vec<(usize, usize, u8)>,
+        /// The pitches (track index, pitch) of sliding notes.
+        pitches:                Vec<(usize, f32)>,
 }
 
 impl TrackerThreadOutput {
@@ -24,12 +38,54 @@ impl TrackerThreadOutput {
             song_pos_s: 0.0,
             cpu: (0.0, 0.0, 0.0),
             events: Vec::new(),
+            launches: Vec::new(),
+            recorded: Vec::new(),
+            pitches: Vec::new(),
             track_notes: Vec::new(),
             audio_scope_samples: Vec::new(),
             audio_scope_done: false,
         }
     }
 
+    /// Stops the note that is still playing on the removed
+    /// track and moves the playing notes of the following tracks.
+    pub fn remove_track(&mut self, track_idx: usize) {
+        if track_idx >= self.track_notes.len() { return; }
+
+        let (note, instr) = self.track_notes.remove(track_idx);
+        if note > 0 {
+            self.events.push((track_idx, 1, note, instr));
+        }
+    }
+
+    /// Starts a preview note. It is not one of the playing notes
+    /// of the tracks, so a note the track plays is not stopped.
+    pub fn start_preview(&mut self, track_idx: usize, note: u8, instr: u8) {
+        self.events.push((track_idx, note, note, instr));
+    }
+
+    /// Stops a note started with start_preview(), unless the
+    /// track plays the same note now.
+    pub fn stop_preview(&mut self, track_idx: usize, note: u8, instr: u8) {
+        if self.track_notes.get(track_idx) == Some(&(note, instr)) { return; }
+        self.events.push((track_idx, 1, note, instr));
+    }
+
+    pub fn insert_track(&mut self, track_idx: usize) {
+        if track_idx >= self.track_notes.len() { return; }
+        self.track_notes.insert(track_idx, (0, 0));
+    }
+
+    pub fn move_track(&mut self, from_idx: usize, to_idx: usize) {
+        let max_idx = if from_idx > to_idx { from_idx } else { to_idx };
+        if max_idx >= self.track_notes.len() {
+            self.track_notes.resize(max_idx + 1, (0, 0));
+        }
+
+        let note = self.track_notes.remove(from_idx);
+        self.track_notes.insert(to_idx, note);
+    }
+
     pub fn collect_audio_scope_samples(&mut self, sample_rate: usize, bufs: &Vec<Vec<f32>>) {
         if bufs.len() != self.audio_scope_samples.len() {
             self.audio_scope_samples.resize(bufs.len(), Vec::new());
@@ -69,22 +125,23 @@ impl OutputHandler for TrackerThreadOutput {
     fn emit_event(&mut self, track_idx: usize, row: &Row) {
         if row.note > 0 {
             if track_idx >= self.track_notes.len() {
-                self.track_notes.resize(track_idx + 1, 0);
+                self.track_notes.resize(track_idx + 1, (0, 0));
             }
 
             if row.note > 1 {
                 self.events.push(
-                    (track_idx, row.note, row.note));
+                    (track_idx, row.note, row.note, row.instr));
             }
 
-            if self.track_notes[track_idx] > 0 {
-                self.events.push((track_idx, 1, self.track_notes[track_idx]));
+            let (playing_note, playing_instr) = self.track_notes[track_idx];
+            if playing_note > 0 {
+                self.events.push((track_idx, 1, playing_note, playing_instr));
             }
 
             if row.note == 1 {
-                self.track_notes[track_idx] = 0;
+                self.track_notes[track_idx] = (0, 0);
             } else {
-                self.track_notes[track_idx] = row.note;
+                self.track_notes[track_idx] = (row.note, row.instr);
             }
         }
         //d// println!("EMIT: {}: {}/{}", track_idx, val, flags);
@@ -96,8 +153,23 @@ impl OutputHandler for TrackerThreadOutput {
     }
 
     fn song_pos(&mut self) -> &mut f32 { return &mut self.song_pos_s; }
+
+    fn emit_launch(&mut self, track_idx: usize, pat_idx: Option<usize>) {
+        self.launches.push((track_idx, pat_idx));
+    }
+
+    fn emit_record(&mut self, track_idx: usize, line: usize, note: u8) {
+        self.recorded.push((track_idx, line, note));
+    }
+
+    fn emit_pitch(&mut self, track_idx: usize, pitch: f32) {
+        self.pitches.push((track_idx, pitch));
+    }
 }
 
+/// Length of a preview note in milliseconds.
+const PREVIEW_MS : usize = 250;
+
 fn calc_cpu_percentage(millis: u128, interval_ms: u128) -> f64 {
     ((millis * 100000)
      / ((interval_ms * 1000) as u128)) as f64 / 1000.0
@@ -110,6 +182,7 @@ struct AudioThreadWLambdaContext {
     pub sim: Simulator,
     pub track_values: std::rc::Rc<std::cell::RefCell<Vec<f32>>>,
     pub sample_rate: usize,
+    pub sync_server: Option<SyncServer>,
 }
 
 fn eval_audio_script(mut msgh: wlambda::threads::MsgHandle, ctxref: std::rc::Rc<std::cell::RefCell<AudioThreadWLambdaContext>>) {
@@ -193,6 +266,27 @@ fn eval_audio_script(mut msgh: wlambda::threads::MsgHandle, ctxref: std::rc::Rc<
             })
         }, Some(2), Some(2));
 
+    genv.borrow_mut().add_func(
+        "sync_server", |env: &mut Env, _argc: usize| {
+            let port = env.arg(0).i();
+            if port < 1 || port > 65535 {
+                return Ok(VVal::err_msg(&format!("bad sync server port: {}", port)));
+            }
+            env.with_user_do(|ctx: &mut AudioThreadWLambdaContext| {
+                match SyncServer::bind(&format!("127.0.0.1:{}", port)) {
+                    Ok(srv) => {
+                        println!("SYNC SERVER: listening on 127.0.0.1:{}", port);
+                        ctx.sync_server = Some(srv);
+                        Ok(VVal::Bol(true))
+                    },
+                    Err(e) => {
+                        Ok(VVal::err_msg(
+                            &format!("can't start sync server on port {}: {}", port, e)))
+                    },
+                }
+            })
+        }, Some(1), Some(1));
+
     let mut wl_eval_ctx =
         wlambda::compiler::EvalContext::new_with_user(genv, ctxref);
 
@@ -230,6 +324,7 @@ pub fn start_tracker_thread(
                 sim:          Simulator::new(),
                 track_values: std::rc::Rc::new(std::cell::RefCell::new(vec![])),
                 sample_rate:  audio_f.get_sample_rate(),
+                sync_server:  None,
             }));
 
         eval_audio_script(msgh, ctxref.clone());
@@ -271,6 +366,12 @@ pub fn start_tracker_thread(
         let mut micros_sum : u128 = 0;
         let mut micros_cnt : u128 = 0;
         let mut audio_scope_counter : usize = 0;
+        // The output names for the sync server are only
+        // rebuilt if the tracks or the output values changed:
+        let mut outputs_changed  = true;
+        let mut sync_value_count = 0;
+        // (track index, note, instrument, ticks until the note off)
+        let mut preview : Option<(usize, u8, u8, usize)> = None;
         loop {
             let now = std::time::Instant::now();
 
@@ -280,20 +381,124 @@ pub fn start_tracker_thread(
             match r {
                 Ok(TrackerSyncMsg::AddTrack(track)) => {
                     t.add_track(track.clone());
+                    outputs_changed = true;
                     println!("THRD: TRACK ADD TRACK");
                 },
-                Ok(TrackerSyncMsg::SetInt(track_idx, line, int)) => {
-                    t.set_int(track_idx, line, int);
+                Ok(TrackerSyncMsg::InsertTrack(track_idx, track)) => {
+                    t.insert_track(track_idx, track);
+                    outputs_changed = true;
+                    o.insert_track(track_idx);
+                    println!("THRD: TRACK INSERT TRACK");
+                },
+                Ok(TrackerSyncMsg::RemoveTrack(track_idx)) => {
+                    t.remove_track(track_idx);
+                    outputs_changed = true;
+                    o.remove_track(track_idx);
+                    out_updated = true;
+                    println!("THRD: TRACK REMOVE TRACK");
+                },
+                Ok(TrackerSyncMsg::MoveTrack(from_idx, to_idx)) => {
+                    t.move_track(from_idx, to_idx);
+                    outputs_changed = true;
+                    o.move_track(from_idx, to_idx);
+                    println!("THRD: TRACK MOVE TRACK");
+                },
+                Ok(TrackerSyncMsg::RenameTrack(track_idx, name)) => {
+                    t.rename_track(track_idx, &name);
+                    outputs_changed = true;
+                    println!("THRD: TRACK RENAME TRACK");
+                },
+                Ok(TrackerSyncMsg::SetTrackKind(track_idx, kind)) => {
+                    t.set_track_kind(track_idx, kind);
+                    outputs_changed = true;
+                    println!("THRD: SET TRACK KIND");
+                },
+                Ok(TrackerSyncMsg::SetMute(track_idx, mute)) => {
+                    t.set_mute(track_idx, mute);
+                    println!("THRD: SET MUTE");
+                },
+                Ok(TrackerSyncMsg::SetSolo(track_idx, solo)) => {
+                    t.set_solo(track_idx, solo);
+                    println!("THRD: SET SOLO");
+                },
+                Ok(TrackerSyncMsg::SetFreezeMutedValues(freeze)) => {
+                    t.set_freeze_muted_values(freeze);
+                },
+                Ok(TrackerSyncMsg::SetInt(track_idx, col, line, int)) => {
+                    t.set_int(track_idx, col, line, int);
                     println!("THRD: SET VAL");
                 },
-                Ok(TrackerSyncMsg::SetValue(track_idx, line, v)) => {
-                    t.set_value(track_idx, line, v);
+                Ok(TrackerSyncMsg::SetValue(track_idx, col, line, v)) => {
+                    t.set_value(track_idx, col, line, v);
                     println!("THRD: SET VAL");
                 },
+                Ok(TrackerSyncMsg::SetValueColumns(track_idx, cols)) => {
+                    t.set_value_columns(track_idx, cols);
+                    outputs_changed = true;
+                    println!("THRD: SET VALUE COLUMNS");
+                },
                 Ok(TrackerSyncMsg::SetNote(track_idx, line, v)) => {
                     t.set_note(track_idx, line, v);
                     println!("THRD: SET NOTE {}", v);
                 },
+                Ok(TrackerSyncMsg::SetInstr(track_idx, line, v)) => {
+                    t.set_instr(track_idx, line, v);
+                    println!("THRD: SET INSTR");
+                },
+                Ok(TrackerSyncMsg::SetInstrument(num, instr)) => {
+                    t.set_instrument(num, instr);
+                    println!("THRD: SET INSTRUMENT {}", num);
+                },
+                Ok(TrackerSyncMsg::SetTuning(tuning)) => {
+                    println!("THRD: SET TUNING {}", tuning.name);
+                    t.set_tuning(tuning);
+                },
+                Ok(TrackerSyncMsg::SetGroove(groove_idx, groove)) => {
+                    t.set_groove(groove_idx, groove);
+                    println!("THRD: SET GROOVE {}", groove_idx);
+                },
+                Ok(TrackerSyncMsg::SetSongGroove(groove_idx)) => {
+                    t.set_song_groove(groove_idx);
+                    println!("THRD: SET SONG GROOVE");
+                },
+                Ok(TrackerSyncMsg::SetTrackGroove(track_idx, groove_idx)) => {
+                    t.set_track_groove(track_idx, groove_idx);
+                    println!("THRD: SET TRACK GROOVE");
+                },
+                Ok(TrackerSyncMsg::PreviewNote(track_idx, note, instr)) => {
+                    // The events are handled from the last one, so
+                    // the previous preview note is stopped first:
+                    o.start_preview(track_idx, note, instr);
+                    if let Some((p_track_idx, p_note, p_instr, _)) = preview {
+                        o.stop_preview(p_track_idx, p_note, p_instr);
+                    }
+
+                    let ticks = PREVIEW_MS / (if t.tick_interval < 1 { 1 } else { t.tick_interval });
+                    preview = Some((track_idx, note, instr, ticks));
+                    out_updated = true;
+                    println!("THRD: PREVIEW NOTE {}", note);
+                },
+                Ok(TrackerSyncMsg::RecordNote(track_idx, note, overdub)) => {
+                    t.record_note_at_play_pos(&mut o, track_idx, note, overdub);
+                    out_updated = true;
+                    println!("THRD: RECORD NOTE");
+                },
+                Ok(TrackerSyncMsg::QueueLaunch(track_idx, launch)) => {
+                    t.queue_launch(track_idx, launch);
+                    println!("THRD: QUEUE LAUNCH");
+                },
+                Ok(TrackerSyncMsg::SetSlide(track_idx, line, slide)) => {
+                    t.set_slide(track_idx, line, slide);
+                    println!("THRD: SET SLIDE");
+                },
+                Ok(TrackerSyncMsg::SetCond(track_idx, line, cond)) => {
+                    t.set_cond(track_idx, line, cond);
+                    println!("THRD: SET COND");
+                },
+                Ok(TrackerSyncMsg::SetSeed(seed)) => {
+                    t.set_seed(seed);
+                    println!("THRD: SET SEED {}", seed);
+                },
                 Ok(TrackerSyncMsg::SetA(track_idx, line, v)) => {
                     t.set_a(track_idx, line, v);
                     println!("THRD: SET A");
@@ -302,12 +507,25 @@ pub fn start_tracker_thread(
                     t.set_b(track_idx, line, v);
                     println!("THRD: SET B");
                 },
+                Ok(TrackerSyncMsg::SetRow(track_idx, line, row)) => {
+                    t.set_row(track_idx, line, row);
+                    println!("THRD: SET ROW");
+                },
                 Ok(TrackerSyncMsg::RemoveValue(track_idx, line)) => {
                     t.remove_value(track_idx, line);
                     println!("THRD: REMO VAL");
                 },
+                Ok(TrackerSyncMsg::SetPatternLen(track_idx, pat_idx, len)) => {
+                    t.set_pattern_len(track_idx, pat_idx, len);
+                    println!("THRD: SET PATTERN LEN");
+                },
+                Ok(TrackerSyncMsg::ResampleLpp(track_idx, lpp)) => {
+                    t.resample_lpp(track_idx, lpp);
+                    println!("THRD: RESAMPLE LPP");
+                },
                 Ok(TrackerSyncMsg::DeserializeContents(track_idx, contents)) => {
                     t.deserialize_contents(track_idx, contents);
+                    outputs_changed = true;
                 },
                 Ok(TrackerSyncMsg::PlayHead(a)) => {
                     match a {
@@ -332,6 +550,10 @@ pub fn start_tracker_thread(
                             t.reset_pos();
                             is_playing = true;
                         },
+                        PlayHeadAction::Seek(line) => {
+                            t.tick_to_line(line, &mut o, &ctx.track_values);
+                            out_updated = true;
+                        },
                         // _ => (),
                     }
                 },
@@ -339,36 +561,103 @@ pub fn start_tracker_thread(
                 Err(std::sync::mpsc::TryRecvError::Disconnected) => return (),
             }
 
+            // Commands of the demo engines connected to the sync server:
+            let sync_cmds =
+                match &mut ctx.sync_server {
+                    Some(srv) => srv.poll(),
+                    None      => vec![],
+                };
+            for cmd in sync_cmds.into_iter() {
+                match cmd {
+                    SyncCommand::Play  => { is_playing = true; },
+                    SyncCommand::Pause => { is_playing = false; },
+                    SyncCommand::Seek(line) => {
+                        t.tick_to_line(line, &mut o, &ctx.track_values);
+                    },
+                }
+                out_updated = true;
+            }
+
             if is_playing {
                 t.tick(&mut o, &ctx.track_values);
                 out_updated = true;
                 //d// println!("THRD: TICK {}", o.pos);
             }
 
+            if let Some((p_track_idx, p_note, p_instr, ticks)) = preview {
+                if ticks == 0 {
+                    o.stop_preview(p_track_idx, p_note, p_instr);
+                    preview = None;
+                    out_updated = true;
+                } else {
+                    preview = Some((p_track_idx, p_note, p_instr, ticks - 1));
+                }
+            }
+
             if out_updated {
                 while !o.events.is_empty() {
                     let e = o.events.pop().unwrap();
-                    // TODO: Implement proper mapping of track->group, maybe
-                    //       use the A value?!
                     let ev =
                         if e.1 == 1 {
                             signals::Event::NoteOff(e.2)
                         } else {
                             signals::Event::NoteOn(e.1)
                         };
-                    ctx.sim.event(0, &ev);
-                    ctx.sim.event(1, &ev);
-                    ctx.sim.event(2, &ev);
-                    ctx.sim.event(3, &ev);
-                    ctx.sim.event(4, &ev);
-                    ctx.sim.event(5, &ev);
-                    ctx.sim.event(6, &ev);
+
+                    // Notes without an instrument go to the
+                    // first signal groups, like before instruments existed.
+                    if let Some(instr) = t.instrument(e.3) {
+                        if e.1 != 1 {
+                            for (op_name, in_name, v) in instr.params.iter() {
+                                if let Some(op_idx) = ctx.sim.get_op_index(op_name) {
+                                    ctx.sim.set_op_input(
+                                        op_idx, in_name, OpIn::Constant(*v), false);
+                                }
+                            }
+
+                            // The frequency of the note in the tuning
+                            // is published along with the note on event:
+                            if let Some((op_name, in_name)) = &instr.freq_input {
+                                if let Some(op_idx) = ctx.sim.get_op_index(op_name) {
+                                    ctx.sim.set_op_input(
+                                        op_idx, in_name,
+                                        OpIn::Constant(t.tuning.freq(e.1)), false);
+                                }
+                            }
+                        }
+
+                        for g in instr.groups.iter() {
+                            ctx.sim.event(*g, &ev);
+                        }
+                    } else {
+                        for g in 0..7 {
+                            ctx.sim.event(g, &ev);
+                        }
+                    }
+                }
+
+                // Sliding notes change the frequency input of the
+                // instrument of the playing note:
+                for (track_idx, pitch) in o.pitches.drain(..) {
+                    let instr =
+                        o.track_notes.get(track_idx).map(|n| n.1).unwrap_or(0);
+
+                    if let Some(instr) = t.instrument(instr) {
+                        if let Some((op_name, in_name)) = &instr.freq_input {
+                            if let Some(op_idx) = ctx.sim.get_op_index(op_name) {
+                                ctx.sim.set_op_input(
+                                    op_idx, in_name,
+                                    OpIn::Constant(t.tuning.pitch_freq(pitch)), false);
+                            }
+                        }
+                    }
                 }
 
                 ctx.sim.exec(o.song_pos_s, rr.clone());
             }
 
-            if is_playing {
+            // Preview notes are also heard while the tracker is paused.
+            if is_playing || preview.is_some() {
                 ctx.sim.render(sample_buf_len, 0, &mut audio_buffers);
                 if audio_scope_counter > ticks_per_audio_scope_update {
                     o.collect_audio_scope_samples(
@@ -384,10 +673,27 @@ pub fn start_tracker_thread(
 
             if out_updated {
                 out_updated = false;
+
+                let values = ctx.track_values.clone();
+                if let Some(srv) = &mut ctx.sync_server {
+                    let count = values.borrow().len();
+                    if outputs_changed || count != sync_value_count {
+                        srv.set_outputs(
+                            output_names(&t.tracks).into_iter()
+                                .filter(|(idx, _)| *idx < count)
+                                .collect());
+                        outputs_changed  = false;
+                        sync_value_count = count;
+                    }
+                    srv.send_frame(o.pos, o.song_pos_s, is_playing, &values.borrow());
+                }
+
                 if let Ok(ref mut m) = ext_out.try_lock() {
                     m.pos        = o.pos;
                     m.song_pos_s = o.song_pos_s;
                     m.cpu        = o.cpu;
+                    m.launches.extend(o.launches.drain(..));
+                    m.recorded.extend(o.recorded.drain(..));
                     if o.audio_scope_done && !m.audio_scope_done {
                         m.audio_scope_done = o.audio_scope_done;
                         std::mem::swap(
@@ -452,12 +758,36 @@ pub fn start_tracker_thread(
 #[derive(Debug, Clone)]
 pub enum TrackerSyncMsg {
     AddTrack(Track),
-    SetValue(usize, usize, f32),
+    InsertTrack(usize, Track),
+    RemoveTrack(usize),
+    MoveTrack(usize, usize),
+    RenameTrack(usize, String),
+    SetTrackKind(usize, TrackKind),
+    SetMute(usize, bool),
+    SetSolo(usize, bool),
+    SetFreezeMutedValues(bool),
+    SetValue(usize, usize, usize, f32),
     SetNote(usize, usize, u8),
+    SetInstr(usize, usize, u8),
+    SetInstrument(u8, Instrument),
+    SetTuning(Tuning),
+    SetGroove(usize, Groove),
+    SetSongGroove(Option<usize>),
+    SetTrackGroove(usize, Option<usize>),
     SetA(usize, usize, u8),
     SetB(usize, usize, u8),
-    SetInt(usize, usize, Interpolation),
+    SetRow(usize, usize, Row),
+    SetCond(usize, usize, Condition),
+    SetSlide(usize, usize, Option<(u8, Interpolation)>),
+    QueueLaunch(usize, Option<Launch>),
+    RecordNote(usize, u8, bool),
+    PreviewNote(usize, u8, u8),
+    SetSeed(u64),
+    SetInt(usize, usize, usize, Interpolation),
+    SetValueColumns(usize, usize),
     RemoveValue(usize, usize),
+    SetPatternLen(usize, usize, usize),
+    ResampleLpp(usize, usize),
     PlayHead(PlayHeadAction),
     DeserializeContents(usize, TrackSerialized),
 }
@@ -477,18 +807,102 @@ impl TrackerSync for ThreadTrackSync {
         self.send.send(TrackerSyncMsg::AddTrack(t))
             .expect("tracker thread communication");
     }
-    fn set_int(&mut self, track_idx: usize, line: usize, int: Interpolation) {
-        self.send.send(TrackerSyncMsg::SetInt(track_idx, line, int))
+    fn insert_track(&mut self, track_idx: usize, t: Track) {
+        self.send.send(TrackerSyncMsg::InsertTrack(track_idx, t))
+            .expect("tracker thread communication");
+    }
+    fn remove_track(&mut self, track_idx: usize) {
+        self.send.send(TrackerSyncMsg::RemoveTrack(track_idx))
+            .expect("tracker thread communication");
+    }
+    fn move_track(&mut self, from_idx: usize, to_idx: usize) {
+        self.send.send(TrackerSyncMsg::MoveTrack(from_idx, to_idx))
+            .expect("tracker thread communication");
+    }
+    fn rename_track(&mut self, track_idx: usize, name: String) {
+        self.send.send(TrackerSyncMsg::RenameTrack(track_idx, name))
+            .expect("tracker thread communication");
+    }
+    fn set_track_kind(&mut self, track_idx: usize, kind: TrackKind) {
+        self.send.send(TrackerSyncMsg::SetTrackKind(track_idx, kind))
+            .expect("tracker thread communication");
+    }
+    fn set_mute(&mut self, track_idx: usize, mute: bool) {
+        self.send.send(TrackerSyncMsg::SetMute(track_idx, mute))
+            .expect("tracker thread communication");
+    }
+    fn set_solo(&mut self, track_idx: usize, solo: bool) {
+        self.send.send(TrackerSyncMsg::SetSolo(track_idx, solo))
+            .expect("tracker thread communication");
+    }
+    fn set_freeze_muted_values(&mut self, freeze: bool) {
+        self.send.send(TrackerSyncMsg::SetFreezeMutedValues(freeze))
+            .expect("tracker thread communication");
+    }
+    fn set_int(&mut self, track_idx: usize, col: usize, line: usize, int: Interpolation) {
+        self.send.send(TrackerSyncMsg::SetInt(track_idx, col, line, int))
+            .expect("tracker thread communication");
+    }
+    fn set_value(&mut self, track_idx: usize, col: usize, line: usize, value: f32) {
+        self.send.send(TrackerSyncMsg::SetValue(track_idx, col, line, value))
             .expect("tracker thread communication");
     }
-    fn set_value(&mut self, track_idx: usize, line: usize, value: f32) {
-        self.send.send(TrackerSyncMsg::SetValue(track_idx, line, value))
+    fn set_value_columns(&mut self, track_idx: usize, cols: usize) {
+        self.send.send(TrackerSyncMsg::SetValueColumns(track_idx, cols))
             .expect("tracker thread communication");
     }
     fn set_note(&mut self, track_idx: usize, line: usize, value: u8) {
         self.send.send(TrackerSyncMsg::SetNote(track_idx, line, value))
             .expect("tracker thread communication");
     }
+    fn set_instr(&mut self, track_idx: usize, line: usize, value: u8) {
+        self.send.send(TrackerSyncMsg::SetInstr(track_idx, line, value))
+            .expect("tracker thread communication");
+    }
+    fn set_instrument(&mut self, num: u8, instr: Instrument) {
+        self.send.send(TrackerSyncMsg::SetInstrument(num, instr))
+            .expect("tracker thread communication");
+    }
+    fn set_tuning(&mut self, tuning: Tuning) {
+        self.send.send(TrackerSyncMsg::SetTuning(tuning))
+            .expect("tracker thread communication");
+    }
+    fn set_groove(&mut self, groove_idx: usize, groove: Groove) {
+        self.send.send(TrackerSyncMsg::SetGroove(groove_idx, groove))
+            .expect("tracker thread communication");
+    }
+    fn set_song_groove(&mut self, groove_idx: Option<usize>) {
+        self.send.send(TrackerSyncMsg::SetSongGroove(groove_idx))
+            .expect("tracker thread communication");
+    }
+    fn set_track_groove(&mut self, track_idx: usize, groove_idx: Option<usize>) {
+        self.send.send(TrackerSyncMsg::SetTrackGroove(track_idx, groove_idx))
+            .expect("tracker thread communication");
+    }
+    fn preview_note(&mut self, track_idx: usize, note: u8, instr: u8) {
+        self.send.send(TrackerSyncMsg::PreviewNote(track_idx, note, instr))
+            .expect("tracker thread communication");
+    }
+    fn record_note(&mut self, track_idx: usize, note: u8, overdub: bool) {
+        self.send.send(TrackerSyncMsg::RecordNote(track_idx, note, overdub))
+            .expect("tracker thread communication");
+    }
+    fn queue_launch(&mut self, track_idx: usize, launch: Option<Launch>) {
+        self.send.send(TrackerSyncMsg::QueueLaunch(track_idx, launch))
+            .expect("tracker thread communication");
+    }
+    fn set_slide(&mut self, track_idx: usize, line: usize, slide: Option<(u8, Interpolation)>) {
+        self.send.send(TrackerSyncMsg::SetSlide(track_idx, line, slide))
+            .expect("tracker thread communication");
+    }
+    fn set_cond(&mut self, track_idx: usize, line: usize, cond: Condition) {
+        self.send.send(TrackerSyncMsg::SetCond(track_idx, line, cond))
+            .expect("tracker thread communication");
+    }
+    fn set_seed(&mut self, seed: u64) {
+        self.send.send(TrackerSyncMsg::SetSeed(seed))
+            .expect("tracker thread communication");
+    }
     fn set_a(&mut self, track_idx: usize, line: usize, value: u8) {
         self.send.send(TrackerSyncMsg::SetA(track_idx, line, value))
             .expect("tracker thread communication");
@@ -497,10 +911,22 @@ impl TrackerSync for ThreadTrackSync {
         self.send.send(TrackerSyncMsg::SetB(track_idx, line, value))
             .expect("tracker thread communication");
     }
+    fn set_row(&mut self, track_idx: usize, line: usize, row: Row) {
+        self.send.send(TrackerSyncMsg::SetRow(track_idx, line, row))
+            .expect("tracker thread communication");
+    }
     fn remove_value(&mut self, track_idx: usize, line: usize) {
         self.send.send(TrackerSyncMsg::RemoveValue(track_idx, line))
             .expect("tracker thread communication");
     }
+    fn set_pattern_len(&mut self, track_idx: usize, pat_idx: usize, len: usize) {
+        self.send.send(TrackerSyncMsg::SetPatternLen(track_idx, pat_idx, len))
+            .expect("tracker thread communication");
+    }
+    fn resample_lpp(&mut self, track_idx: usize, lpp: usize) {
+        self.send.send(TrackerSyncMsg::ResampleLpp(track_idx, lpp))
+            .expect("tracker thread communication");
+    }
     fn play_head(&mut self, act: PlayHeadAction) {
         self.send.send(TrackerSyncMsg::PlayHead(act))
             .expect("tracker thread communication");
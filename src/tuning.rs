@@ -0,0 +1,230 @@
+use serde::Serialize;
+use serde::Deserialize;
+
+/// A tuning maps the note numbers of the tracks to frequencies.
+/// The scale is given like in a Scala `.scl` file and the mapping of
+/// the note numbers to the scale degrees like in a Scala `.kbm` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tuning {
+    pub name:        String,
+    /// The pitches of the scale degrees 1 to n in cents,
+    /// the last one is the period of the scale (usually the octave).
+    pub cents:       Vec<f64>,
+    /// The note number that plays scale degree 0.
+    pub middle_note: u8,
+    /// The note number that plays `ref_freq`.
+    pub ref_note:    u8,
+    pub ref_freq:    f64,
+    /// The scale degrees of the keys starting at the middle note,
+    /// None for keys that play nothing. If empty, each note
+    /// number plays the next scale degree.
+    pub key_map:     Vec<Option<usize>>,
+    /// The scale degree that the key map repeats at.
+    pub map_octave:  usize,
+}
+
+impl std::default::Default for Tuning {
+    fn default() -> Self { Tuning::equal("12-TET", 12) }
+}
+
+/// Returns the next line of a Scala file that is not a comment.
+fn next_line<'a, I>(lines: &mut I) -> Option<&'a str>
+    where I: Iterator<Item = &'a str> {
+
+    lines.map(|l| l.trim()).find(|l| !l.starts_with('!'))
+}
+
+/// Parses a pitch of a `.scl` file, either in cents (with a '.')
+/// or as ratio like `3/2` or `2`.
+fn parse_pitch(s: &str) -> Result<f64, String> {
+    let s = s.split_whitespace().next().unwrap_or("");
+
+    if s.contains('.') {
+        return s.parse::<f64>()
+                .map_err(|e| format!("bad cents value '{}': {}", s, e));
+    }
+
+    let mut parts = s.splitn(2, '/');
+    let num = parts.next().unwrap_or("").parse::<f64>();
+    let den = parts.next().unwrap_or("1").parse::<f64>();
+    match (num, den) {
+        (Ok(num), Ok(den)) if num > 0.0 && den > 0.0 =>
+            Ok(1200.0 * (num / den).log2()),
+        _ => Err(format!("bad ratio '{}'", s)),
+    }
+}
+
+fn parse_num<T>(line: Option<&str>, what: &str) -> Result<T, String>
+    where T: std::str::FromStr {
+
+    let s = line.ok_or(format!("missing {}", what))?;
+    let s = s.split_whitespace().next().unwrap_or("");
+    s.parse::<T>().map_err(|_| format!("bad {}: '{}'", what, s))
+}
+
+/// Returns the name of a note of a scale with `steps` degrees, counted
+/// from the `middle` note which is in octave 4.
+/// Scales with 12 degrees use the usual note names.
+pub fn note_name(note: u8, steps: usize, middle: u8) -> String {
+    const NOTE_NAMES : &'static [&str] =
+        &["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+    let steps = if steps < 1 { 1 } else { steps as i32 };
+    let d     = note as i32 - middle as i32;
+    let mut octave = d / steps;
+    let mut degree = d % steps;
+    if degree < 0 {
+        degree += steps;
+        octave -= 1;
+    }
+
+    if steps == 12 {
+        format!("{}{}", NOTE_NAMES[degree as usize], octave + 4)
+    } else {
+        format!("{}.{}", degree, octave + 4)
+    }
+}
+
+impl Tuning {
+    /// Creates an equal temperament with `steps` steps per octave,
+    /// note 60 is C4 and note 69 is A4 with 440 Hz, like in MIDI.
+    pub fn equal(name: &str, steps: usize) -> Self {
+        let steps = if steps < 1 { 1 } else { steps };
+        Tuning {
+            name:        String::from(name),
+            cents:       (1..=steps).map(|i| 1200.0 * i as f64 / steps as f64).collect(),
+            middle_note: 60,
+            ref_note:    69,
+            ref_freq:    440.0,
+            key_map:     Vec::new(),
+            map_octave:  steps,
+        }
+    }
+
+    /// Parses the contents of a Scala `.scl` file. The keyboard
+    /// mapping is linear, see apply_kbm().
+    pub fn from_scl(scl: &str) -> Result<Self, String> {
+        let mut lines = scl.lines();
+
+        let name  = next_line(&mut lines).ok_or("missing description")?;
+        let count : usize = parse_num(next_line(&mut lines), "note count")?;
+        if count < 1 {
+            return Err(String::from("scale without notes"));
+        }
+
+        let mut cents = Vec::new();
+        for _ in 0..count {
+            let line = next_line(&mut lines).ok_or("missing pitch")?;
+            cents.push(parse_pitch(line)?);
+        }
+
+        let mut t = Tuning::equal(name, count);
+        t.cents = cents;
+        Ok(t)
+    }
+
+    /// Applies the keyboard mapping of a Scala `.kbm` file.
+    pub fn apply_kbm(&mut self, kbm: &str) -> Result<(), String> {
+        let mut lines = kbm.lines();
+
+        let map_size : usize = parse_num(next_line(&mut lines), "map size")?;
+        let _first   : u8    = parse_num(next_line(&mut lines), "first note")?;
+        let _last    : u8    = parse_num(next_line(&mut lines), "last note")?;
+        let middle   : u8    = parse_num(next_line(&mut lines), "middle note")?;
+        let ref_note : u8    = parse_num(next_line(&mut lines), "reference note")?;
+        let ref_freq : f64   = parse_num(next_line(&mut lines), "reference frequency")?;
+        let octave   : usize = parse_num(next_line(&mut lines), "octave degree")?;
+
+        let mut key_map = Vec::new();
+        for _ in 0..map_size {
+            match next_line(&mut lines) {
+                Some(l) if l.starts_with('x') => key_map.push(None),
+                Some(l) => key_map.push(Some(parse_num(Some(l), "key mapping")?)),
+                // Missing entries at the end are unmapped keys:
+                None => key_map.push(None),
+            }
+        }
+
+        self.middle_note = middle;
+        self.ref_note    = ref_note;
+        self.ref_freq    = ref_freq;
+        self.key_map     = key_map;
+        self.map_octave  = if octave > 0 { octave } else { self.steps() };
+        Ok(())
+    }
+
+    /// The number of degrees of the scale.
+    pub fn steps(&self) -> usize { self.cents.len() }
+
+    /// Returns the scale degree the note plays, counted from the
+    /// middle note, or None if the note is not mapped.
+    fn degree(&self, note: u8) -> Option<i32> {
+        let d = note as i32 - self.middle_note as i32;
+        if self.key_map.is_empty() { return Some(d); }
+
+        let size = self.key_map.len() as i32;
+        let mut octave = d / size;
+        let mut idx    = d % size;
+        if idx < 0 {
+            idx    += size;
+            octave -= 1;
+        }
+
+        let deg = self.key_map[idx as usize]?;
+        Some(octave * self.map_octave as i32 + deg as i32)
+    }
+
+    /// Returns the pitch of the scale degree in cents above degree 0.
+    fn degree_cents(&self, degree: i32) -> f64 {
+        let steps = self.steps() as i32;
+        if steps == 0 { return 0.0; }
+
+        let period = self.cents[(steps - 1) as usize];
+        let mut octave = degree / steps;
+        let mut idx    = degree % steps;
+        if idx < 0 {
+            idx    += steps;
+            octave -= 1;
+        }
+
+        let c = if idx == 0 { 0.0 } else { self.cents[(idx - 1) as usize] };
+        octave as f64 * period + c
+    }
+
+    /// Returns the frequency of the note in Hz, or 0.0 if the note
+    /// is not mapped to a scale degree.
+    pub fn freq(&self, note: u8) -> f32 {
+        let deg = match self.degree(note) { Some(d) => d, None => return 0.0 };
+        let ref_cents =
+            self.degree(self.ref_note)
+                .map(|d| self.degree_cents(d))
+                .unwrap_or(0.0);
+
+        (self.ref_freq
+         * 2.0_f64.powf((self.degree_cents(deg) - ref_cents) / 1200.0))
+        as f32
+    }
+
+    /// Returns the frequency of a pitch between notes, like a
+    /// sliding note, see Track::pitch().
+    pub fn pitch_freq(&self, pitch: f32) -> f32 {
+        let pitch = if pitch < 0.0 { 0.0 } else if pitch > 255.0 { 255.0 } else { pitch };
+        let note  = pitch.floor() as u8;
+        let x     = pitch - note as f32;
+
+        let f0 = self.freq(note);
+        if x == 0.0 || note == 255 { return f0; }
+        let f1 = self.freq(note + 1);
+
+        if f0 <= 0.0 || f1 <= 0.0 {
+            if x < 0.5 { f0 } else { f1 }
+        } else {
+            f0 * (f1 / f0).powf(x)
+        }
+    }
+
+    /// The name of the note in this tuning, see note_name().
+    pub fn note_name(&self, note: u8) -> String {
+        note_name(note, self.steps(), self.middle_note)
+    }
+}
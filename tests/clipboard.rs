@@ -0,0 +1,33 @@
+use wdem_tracker::tracker::*;
+use wdem_tracker::tracker_editor::TrackerEditor;
+use wdem_tracker::track::*;
+use wdem_tracker::mpt_clipboard::rows_to_mpt;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+#[test]
+fn paste_keeps_values_and_stops_at_the_end() {
+    let tracker = Rc::new(RefCell::new(Tracker::new(TrackerNopSync { })));
+    tracker.borrow_mut().add_track(Track::new("mixed", 4));
+    tracker.borrow_mut().set_value(0, 0, 1, 0.5);
+    tracker.borrow_mut().set_note(0, 2, 50);
+
+    let mut rows = vec![Row::new(); 6];
+    rows[0].note  = 60;
+    rows[0].instr = 2;
+    rows[5].note  = 62;
+    let text = rows_to_mpt(&[rows]);
+
+    let mut editor = TrackerEditor::new(tracker.clone());
+    assert_eq!(editor.paste_block(&text), Ok(1));
+
+    let tr = tracker.borrow();
+    let t  = &tr.tracks[0];
+    assert_eq!(t.line_count(), 4);
+
+    let stored : Vec<usize> = t.patterns[0].iter().map(|(line, _)| *line).collect();
+    assert_eq!(stored, vec![0, 1]);
+    assert_eq!(t.patterns[0].row(0).note, 60);
+    assert_eq!(t.patterns[0].row(0).instr, 2);
+    assert_eq!(t.patterns[0].row(1).value_at(0), Some((0.5, Interpolation::Step)));
+}
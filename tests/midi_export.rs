@@ -0,0 +1,63 @@
+use wdem_tracker::track::*;
+use wdem_tracker::midi::*;
+
+fn notes(mf: &MidiFile, track_idx: usize) -> Vec<(u64, MidiEventKind)> {
+    mf.tracks[track_idx].events.iter()
+        .filter(|e| matches!(e.kind,
+                             MidiEventKind::NoteOn(_, _) | MidiEventKind::NoteOff(_)))
+        .map(|e| (e.tick, e.kind))
+        .collect()
+}
+
+#[test]
+fn export_skips_notes_above_the_midi_range() {
+    let mut t = Track::new_kind("lead", 8, TrackKind::Note);
+    t.set_note(0, 60);
+    t.set_note(2, 200);
+    t.set_note(4, 127);
+
+    let (data, skipped) =
+        export_song(&[t], 4, 2, 5, ValueOut::Control(1)).unwrap();
+    assert_eq!(skipped, 1);
+
+    let mf = MidiFile::parse(&data).unwrap();
+    assert_eq!(notes(&mf, 1), vec![
+        (0,  MidiEventKind::NoteOn(60, 100)),
+        (4,  MidiEventKind::NoteOff(60)),
+        (8,  MidiEventKind::NoteOn(127, 100)),
+        (16, MidiEventKind::NoteOff(127)),
+    ]);
+}
+
+#[test]
+fn export_sends_triggers_as_notes() {
+    let mut t = Track::new_kind("kick", 4, TrackKind::Trigger);
+    t.set_a(1, 0xFF);
+    t.set_a(3, 1);
+
+    let (data, _) = export_song(&[t], 4, 2, 5, ValueOut::Control(1)).unwrap();
+    let mf = MidiFile::parse(&data).unwrap();
+    assert_eq!(notes(&mf, 1), vec![
+        (2, MidiEventKind::NoteOn(60, 127)),
+        (4, MidiEventKind::NoteOff(60)),
+        (6, MidiEventKind::NoteOn(60, 1)),
+        (8, MidiEventKind::NoteOff(60)),
+    ]);
+}
+
+#[test]
+fn export_rejects_controllers_outside_of_the_range() {
+    let mut t = Track::new("values", 4);
+    t.set_value_columns(3);
+
+    assert!(export_song(&[t.clone()], 4, 2, 5, ValueOut::Control(117)).is_ok());
+    assert!(export_song(&[t.clone()], 4, 2, 5, ValueOut::Control(118)).is_err());
+    assert!(export_song(&[t.clone()], 4, 2, 5, ValueOut::Control(0)).is_err());
+    assert!(export_song(&[t.clone()], 4, 2, 5, ValueOut::PitchBend).is_ok());
+
+    // Tracks without value columns need no controllers:
+    let n = Track::new_kind("lead", 4, TrackKind::Note);
+    assert!(export_song(&[n], 4, 2, 5, ValueOut::Control(0)).is_ok());
+
+    assert!(export_song(&[t], 200, 200, 5, ValueOut::Control(1)).is_err());
+}
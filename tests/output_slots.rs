@@ -0,0 +1,74 @@
+use wdem_tracker::tracker::*;
+use wdem_tracker::track::*;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+struct Output { pos: f32 }
+
+impl OutputHandler for Output {
+    fn emit_event(&mut self, _track_idx: usize, _row: &Row) { }
+    fn emit_play_line(&mut self, _play_line: i32) { }
+    fn song_pos(&mut self) -> &mut f32 { &mut self.pos }
+}
+
+fn tick(tr: &mut Tracker<TrackerNopSync>, values: &Rc<RefCell<Vec<f32>>>) -> Vec<f32> {
+    tr.tick(&mut Output { pos: 0.0 }, values);
+    let v = values.borrow().clone();
+    v
+}
+
+#[test]
+fn tracks_keep_their_registers() {
+    let mut tr = Tracker::new(TrackerNopSync { });
+    for (i, v) in [0.1, 0.2, 0.3].iter().enumerate() {
+        tr.add_track(Track::new(&format!("t{}", i), 8));
+        tr.set_value(i, 0, 0, *v);
+    }
+    let values = Rc::new(RefCell::new(vec![0.0; 5]));
+    assert_eq!(tick(&mut tr, &values), vec![0.1, 0.2, 0.3, 0.0, 0.0]);
+
+    tr.move_track(0, 2);
+    assert_eq!(tick(&mut tr, &values), vec![0.1, 0.2, 0.3, 0.0, 0.0]);
+
+    // The register of a removed track is reset:
+    tr.remove_track(0);
+    assert_eq!(tick(&mut tr, &values), vec![0.1, 0.0, 0.3, 0.0, 0.0]);
+
+    // New outputs take the lowest free registers:
+    let mut t = Track::new("new", 8);
+    t.set_value_columns(2);
+    tr.insert_track(0, t);
+    tr.set_value(0, 0, 0, 0.7);
+    tr.set_value(0, 1, 0, 0.8);
+    assert_eq!(tr.tracks[0].out_slots, vec![1, 3]);
+    assert_eq!(tick(&mut tr, &values), vec![0.1, 0.7, 0.3, 0.8, 0.0]);
+
+    // The register of a removed value column is reset:
+    tr.set_value_columns(0, 1);
+    assert_eq!(tr.tracks[0].out_slots, vec![1]);
+    assert_eq!(tick(&mut tr, &values)[3], 0.0);
+}
+
+#[test]
+fn muted_triggers_send_no_pulses() {
+    let mut tr = Tracker::new(TrackerNopSync { });
+    tr.tpl = 1;
+    tr.add_track(Track::new_kind("kick", 4, TrackKind::Trigger));
+    tr.add_track(Track::new("fade", 4));
+    for line in 0..4 {
+        tr.set_a(0, line, 0xFF);
+        tr.set_value(1, 0, line, line as f32);
+    }
+    tr.set_freeze_muted_values(true);
+
+    let values = Rc::new(RefCell::new(vec![0.0; 2]));
+    let v = tick(&mut tr, &values);
+    assert_eq!(v[0], 1.0);
+
+    // The value of the other muted track is frozen:
+    tr.set_mute(0, true);
+    tr.set_mute(1, true);
+    assert_eq!(tick(&mut tr, &values), vec![0.0, v[1]]);
+    tr.set_mute(0, false);
+    assert_eq!(tick(&mut tr, &values), vec![1.0, v[1]]);
+}
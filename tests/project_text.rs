@@ -0,0 +1,105 @@
+use wdem_tracker::track::*;
+use wdem_tracker::groove::Groove;
+use wdem_tracker::project_text::{project_to_text, text_to_project};
+use serde_json::{json, Map, Value};
+
+fn song_tracks() -> Vec<Track> {
+    let mut mixed = Track::new("mixed", 8);
+    mixed.set_value_columns(3);
+    mixed.touch_pattern_idx(1);
+    mixed.set_pattern_len(1, 5);
+    mixed.arrangement = vec![0, 1, 1, 0];
+    mixed.set_value(0, 0, 0.25);
+    mixed.set_int(0, 0, Interpolation::Lerp);
+    // A row with a hole in the value columns:
+    mixed.set_value(2, 2, -1.5);
+    mixed.set_int(2, 2, Interpolation::Exp);
+    mixed.set_value(1, 3, 0.1);
+    mixed.set_int(1, 3, Interpolation::SStep);
+    mixed.set_value(0, 5, 1.0e-3);
+    mixed.set_int(0, 5, Interpolation::Empty);
+    mixed.set_note(1, 60);
+    mixed.set_instr(1, 2);
+    mixed.set_a(1, 0x7F);
+    mixed.set_b(1, 0xFF);
+    mixed.set_note(4, 1);
+    mixed.set_note(9, 62);
+    mixed.set_cond(9, Condition::EveryNth(3));
+    mixed.groove = Some(0);
+    mixed.solo   = true;
+
+    let mut note = Track::new_kind("lead line", 4, TrackKind::Note);
+    note.set_note(0, 48);
+    note.set_cond(0, Condition::FirstPass);
+    note.set_note(2, 55);
+    note.set_slide(2, Some((3, Interpolation::SStep)));
+    note.set_cond(2, Condition::Probability(40));
+    note.set_note(3, 1);
+    note.set_cond(3, Condition::NotFirstPass);
+    note.set_pattern_len(0, 12);
+    note.mute = true;
+
+    let mut auto =
+        Track::new_kind("cut", 6, TrackKind::Automation { min: -0.5, max: 2.0 });
+    auto.set_value(0, 0, -0.5);
+    auto.set_int(0, 0, Interpolation::Step);
+    auto.set_value(0, 4, 2.0);
+
+    let mut trig = Track::new_kind("kick", 4, TrackKind::Trigger);
+    trig.set_a(0, 0xFF);
+    trig.set_a(2, 0x20);
+
+    vec![mixed, note, auto, trig]
+}
+
+fn song_project() -> Map<String, Value> {
+    let mut project = Map::new();
+    project.insert(String::from("grooves"),
+        json!([Groove::new("swing", vec![0.0, 0.33])]));
+    project.insert(String::from("groove"), json!(0));
+    project.insert(String::from("seed"),   json!(1234));
+    project.insert(String::from("op_inputs"),
+        json!([["sin", [["freq", {"Constant": 440.0}]]]]));
+    project
+}
+
+#[test]
+fn text_project_round_trip_is_lossless() {
+    let tracks  = song_tracks();
+    let project = song_project();
+    let ser : Vec<TrackSerialized> =
+        tracks.iter().map(|t| t.serialize_contents()).collect();
+
+    let text = project_to_text(&project, &ser);
+    let (project_back, ser_back) = text_to_project(&text).unwrap();
+
+    assert_eq!(project_back, project);
+    assert_eq!(ser_back, ser);
+
+    for (t, ts) in tracks.iter().zip(ser_back.iter()) {
+        let mut back = Track::new(&ts.name, 1);
+        back.deserialize_contents(ts);
+        assert_eq!(back.serialize_contents(), t.serialize_contents());
+        for line in 0..t.line_count() {
+            assert_eq!(back.row_checked(line), t.row_checked(line));
+        }
+    }
+}
+
+#[test]
+fn text_project_keeps_empty_arrays() {
+    let mut project = song_project();
+    project.insert(String::from("grooves"),     json!([]));
+    project.insert(String::from("instruments"), json!([]));
+    project.insert(String::from("op_inputs"),   json!([]));
+
+    let text = project_to_text(&project, &[]);
+    let (project_back, ser_back) = text_to_project(&text).unwrap();
+    assert_eq!(project_back, project);
+    assert!(ser_back.is_empty());
+}
+
+#[test]
+fn text_project_rejects_other_files() {
+    assert!(text_to_project("{\"tracks\": []}").is_err());
+}
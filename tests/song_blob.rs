@@ -0,0 +1,177 @@
+use wdem_tracker::tracker::*;
+use wdem_tracker::track::*;
+use wdem_tracker::song_blob::export_song_blob;
+use wdem_tracker::song_player::*;
+use serde_json::json;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+struct Output {
+    events:  Vec<(usize, u8, u8, u8, u8)>,
+    pitches: Vec<(usize, f32)>,
+    pos:     f32,
+}
+
+impl OutputHandler for Output {
+    fn emit_event(&mut self, track_idx: usize, row: &Row) {
+        self.events.push((track_idx, row.note, row.instr, row.a, row.b));
+    }
+    fn emit_play_line(&mut self, _play_line: i32) { }
+    fn song_pos(&mut self) -> &mut f32 { &mut self.pos }
+    fn emit_pitch(&mut self, track_idx: usize, pitch: f32) {
+        self.pitches.push((track_idx, pitch));
+    }
+}
+
+fn song() -> Tracker<TrackerNopSync> {
+    let mut tr = Tracker::new(TrackerNopSync { });
+    tr.lpb = 4;
+    tr.tpl = 4;
+
+    let mut mixed = Track::new("mixed", 8);
+    mixed.set_value_columns(2);
+    mixed.touch_pattern_idx(1);
+    mixed.set_pattern_len(1, 6);
+    mixed.arrangement = vec![0, 1, 0, 1, 1];
+    tr.add_track(mixed);
+    tr.add_track(Track::new_kind("lead", 16, TrackKind::Note));
+    tr.add_track(Track::new_kind("kick", 16, TrackKind::Trigger));
+    tr.add_track(Track::new_kind("cut", 16, TrackKind::Automation { min: 0.2, max: 0.8 }));
+    tr.add_track(Track::new_kind("muted", 16, TrackKind::Note));
+
+    tr.set_value(0, 0, 2, 0.5);
+    tr.set_int(0, 0, 2, Interpolation::Lerp);
+    tr.set_value(0, 0, 9, 1.0);
+    tr.set_int(0, 0, 9, Interpolation::SStep);
+    tr.set_value(0, 0, 13, 0.1);
+    tr.set_int(0, 0, 13, Interpolation::Exp);
+    tr.set_value(0, 1, 5, 0.7);
+    tr.set_instr(0, 1, 3);
+    tr.set_note(0, 3, 62);
+    tr.set_note(0, 6, 1);
+
+    tr.set_note(1, 0, 60);
+    tr.set_instr(1, 0, 2);
+    tr.set_note(1, 4, 67);
+    tr.set_slide(1, 4, Some((3, Interpolation::Lerp)));
+    tr.set_note(1, 10, 1);
+    tr.set_note(1, 12, 48);
+    tr.set_a(1, 12, 0x40);
+    tr.set_b(1, 12, 0x10);
+
+    tr.set_a(2, 1, 0xFF);
+    tr.set_a(2, 5, 0x80);
+
+    tr.set_value(3, 0, 0, 0.0);
+    tr.set_int(3, 0, 0, Interpolation::Lerp);
+    tr.set_value(3, 0, 7, 1.0);
+
+    tr.set_note(4, 2, 50);
+    tr.set_mute(4, true);
+
+    tr.add_track(Track::new_kind("muted kick", 16, TrackKind::Trigger));
+    tr.set_a(5, 3, 0xFF);
+    tr.set_mute(5, true);
+    tr
+}
+
+#[test]
+fn player_plays_like_the_tracker() {
+    let mut tr = song();
+    let ops    = json!([["sin", [["freq", {"Constant": 440.0}],
+                                 ["amp",  {"RegMix2": [1, 2, 0.5]}]]]]);
+    let blob =
+        export_song_blob(&tr.tracks, tr.lpb, tr.tpl, tr.tick_interval, tr.groove, &ops)
+            .unwrap();
+
+    let mut p = SongPlayer::new(&blob).unwrap();
+    assert_eq!(p.song_lines(), tr.max_line_count());
+
+    let inputs : Vec<(&str, &str, OpInValue)> = p.op_inputs().collect();
+    assert_eq!(inputs, vec![
+        ("sin", "freq", OpInValue::Constant(440.0)),
+        ("sin", "amp",  OpInValue::RegMix2(1, 2, 0.5)),
+    ]);
+
+    let out_count = p.output_count();
+    let values    = Rc::new(RefCell::new(vec![0.0; out_count]));
+    let mut pvals = vec![0.0; out_count];
+    let mut out   = Output { events: vec![], pitches: vec![], pos: 0.0 };
+
+    // Twice through the song, to also compare the restart:
+    let ticks = 2 * tr.max_line_count() * tr.tpl + 3;
+    for tick in 0..ticks {
+        out.events.clear();
+        out.pitches.clear();
+        tr.tick(&mut out, &values);
+
+        let mut events = vec![];
+        p.tick(|track_idx, e| events.push((track_idx, e.note, e.instr, e.a, e.b)));
+        p.output_values(&mut pvals);
+
+        assert_eq!(events, out.events, "events at tick {}", tick);
+        assert_eq!(p.play_line(), tr.play_line, "line at tick {}", tick);
+        assert!((p.song_pos_in_s() - out.pos).abs() < 1e-6, "song pos at tick {}", tick);
+        for (i, (a, b)) in values.borrow().iter().zip(pvals.iter()).enumerate() {
+            assert!((a - b).abs() < 1e-6,
+                    "output {} at tick {}: {} != {}", i, tick, a, b);
+        }
+        for (track_idx, pitch) in out.pitches.iter() {
+            let ppitch = p.pitch(*track_idx).unwrap();
+            assert!((ppitch - pitch).abs() < 1e-4, "pitch at tick {}", tick);
+        }
+    }
+}
+
+#[test]
+fn player_rejects_broken_blobs() {
+    let tr   = song();
+    let blob =
+        export_song_blob(&tr.tracks, tr.lpb, tr.tpl, tr.tick_interval, tr.groove, &json!([]))
+            .unwrap();
+
+    for len in 0..blob.len() {
+        if let Some(mut p) = SongPlayer::new(&blob[0..len]) {
+            for _ in 0..100 { p.tick(|_, _| ()); }
+        }
+    }
+
+    // A number that does not fit into an usize and huge pattern sizes:
+    let mut bad = BLOB_MAGIC.to_vec();
+    bad.extend_from_slice(&[0xFF; 12]);
+    bad.push(0x01);
+    assert!(SongPlayer::new(&bad).is_none());
+
+    let mut bad = BLOB_MAGIC.to_vec();
+    bad.extend_from_slice(&[4, 4, 5, 16, 1, KIND_MIXED, 0, 1, 0, 1]);
+    push_varint(&mut bad, usize::MAX);
+    assert!(SongPlayer::new(&bad).is_none());
+}
+
+fn push_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+#[test]
+fn export_rejects_what_the_player_does_not_play() {
+    let export = |tr: &Tracker<TrackerNopSync>| {
+        export_song_blob(&tr.tracks, tr.lpb, tr.tpl, tr.tick_interval, tr.groove, &json!([]))
+    };
+
+    let mut tr = song();
+    tr.set_cond(1, 4, Condition::NotFirstPass);
+    assert!(export(&tr).is_err());
+    tr.set_cond(1, 4, Condition::Always);
+    assert!(export(&tr).is_ok());
+
+    tr.set_track_groove(2, Some(0));
+    assert!(export(&tr).is_err());
+    tr.set_track_groove(2, None);
+
+    tr.set_song_groove(Some(0));
+    assert!(export(&tr).is_err());
+}
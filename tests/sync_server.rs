@@ -0,0 +1,105 @@
+use wdem_tracker::sync_server::*;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+fn connect(srv: &mut SyncServer) -> (TcpStream, BufReader<TcpStream>) {
+    let clients = srv.client_count();
+    let stream  = TcpStream::connect(srv.local_addr().unwrap()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let reader = BufReader::new(stream.try_clone().unwrap());
+
+    let start = Instant::now();
+    while srv.client_count() == clients {
+        assert!(srv.poll().is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5), "client not accepted");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    (stream, reader)
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line
+}
+
+/// Polls the server until `count` commands arrived.
+fn poll_commands(srv: &mut SyncServer, count: usize) -> Vec<SyncCommand> {
+    let start    = Instant::now();
+    let mut cmds = Vec::new();
+    while cmds.len() < count {
+        cmds.extend(srv.poll());
+        assert!(start.elapsed() < Duration::from_secs(5), "got only {:?}", cmds);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    cmds
+}
+
+#[test]
+fn server_sends_hello_outputs_and_frames() {
+    let mut srv = SyncServer::bind("127.0.0.1:0").unwrap();
+    srv.set_outputs(vec![(0, String::from("fade")), (2, String::from("cam x:1"))]);
+
+    let (_stream, mut reader) = connect(&mut srv);
+    assert_eq!(read_line(&mut reader),
+               format!("hello wdem-tracker {}\n", SYNC_PROTOCOL_VERSION));
+    assert_eq!(read_line(&mut reader), "outputs 2\n");
+    assert_eq!(read_line(&mut reader), "out 0 fade\n");
+    assert_eq!(read_line(&mut reader), "out 2 cam x:1\n");
+
+    srv.send_frame(3, 0.5, true, &[0.25, 0.0, -1.5]);
+    assert_eq!(read_line(&mut reader), "frame 3 0.5 1 0.25 0 -1.5\n");
+
+    // Unchanged outputs are not sent again:
+    srv.set_outputs(vec![(0, String::from("fade")), (2, String::from("cam x:1"))]);
+    srv.send_frame(-1, 0.0, false, &[]);
+    assert_eq!(read_line(&mut reader), "frame -1 0 0\n");
+
+    srv.set_outputs(vec![(1, String::from("kick"))]);
+    assert_eq!(read_line(&mut reader), "outputs 1\n");
+    assert_eq!(read_line(&mut reader), "out 1 kick\n");
+}
+
+#[test]
+fn server_parses_commands() {
+    let mut srv = SyncServer::bind("127.0.0.1:0").unwrap();
+    let (mut stream, mut reader) = connect(&mut srv);
+    assert_eq!(read_line(&mut reader), "hello wdem-tracker 1\n");
+    assert_eq!(read_line(&mut reader), "outputs 0\n");
+
+    stream.write_all(b"play\npause\n\nseek 12\nse").unwrap();
+    stream.flush().unwrap();
+    assert_eq!(poll_commands(&mut srv, 3),
+               vec![SyncCommand::Play, SyncCommand::Pause, SyncCommand::Seek(12)]);
+
+    // A command that arrives in two parts:
+    stream.write_all(b"ek 3\r\n").unwrap();
+    assert_eq!(poll_commands(&mut srv, 1), vec![SyncCommand::Seek(3)]);
+
+    stream.write_all(b"stop\nseek x\nseek -1\nseek\nplay\n").unwrap();
+    assert_eq!(poll_commands(&mut srv, 1), vec![SyncCommand::Play]);
+    assert_eq!(read_line(&mut reader), "error unknown command 'stop'\n");
+    assert_eq!(read_line(&mut reader), "error bad line in 'seek x'\n");
+    assert_eq!(read_line(&mut reader), "error bad line in 'seek -1'\n");
+    assert_eq!(read_line(&mut reader), "error bad line in 'seek'\n");
+}
+
+#[test]
+fn server_drops_closed_clients() {
+    let mut srv = SyncServer::bind("127.0.0.1:0").unwrap();
+    let (stream, reader) = connect(&mut srv);
+    let (_stream2, _reader2) = connect(&mut srv);
+    assert_eq!(srv.client_count(), 2);
+
+    drop(reader);
+    drop(stream);
+
+    let start = Instant::now();
+    while srv.client_count() > 1 {
+        srv.poll();
+        assert!(start.elapsed() < Duration::from_secs(5), "client not dropped");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(srv.client_count(), 1);
+}
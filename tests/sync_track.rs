@@ -0,0 +1,33 @@
+use wdem_tracker::track::*;
+use wdem_tracker::sync_track::*;
+
+#[test]
+fn rocket_file_names_are_encoded_like_rocket() {
+    assert_eq!(rocket_track_filename("sync", "cam.y_2"), "sync_cam.y_2.track");
+    assert_eq!(rocket_track_filename("sync", "cam x"),   "sync_cam-20x.track");
+    assert_eq!(rocket_track_filename("sync", "fade-in"), "sync_fade-2Din.track");
+    assert_eq!(rocket_track_filename("sync", "fx:2"),    "sync_fx-3A2.track");
+    assert_eq!(rocket_track_filename("sync", "grün"),    "sync_gr-C3-BCn.track");
+}
+
+#[test]
+fn rocket_track_has_the_keys_of_the_column() {
+    let mut t = Track::new("fade in", 8);
+    t.set_value(0, 2, 0.5);
+    t.set_int(0, 2, Interpolation::Lerp);
+    t.set_value(0, 5, 1.0);
+
+    let st = SyncTrack::from_track(&t, 0, "fade in");
+    let data = st.to_rocket();
+
+    // A step to 0.0 before the first key, the keys
+    // and 0.0 on the last line:
+    let mut expected = vec![];
+    expected.extend_from_slice(&4u32.to_le_bytes());
+    for (row, value, typ) in [(0u32, 0.0f32, 0u8), (2, 0.5, 1), (5, 1.0, 0), (7, 0.0, 0)].iter() {
+        expected.extend_from_slice(&row.to_le_bytes());
+        expected.extend_from_slice(&value.to_bits().to_le_bytes());
+        expected.push(*typ);
+    }
+    assert_eq!(data, expected);
+}
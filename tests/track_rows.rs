@@ -0,0 +1,39 @@
+use wdem_tracker::track::*;
+use wdem_tracker::pattern::Pattern;
+
+fn stored_rows(t: &Track) -> usize {
+    t.patterns.iter().map(|p| p.iter().count()).sum()
+}
+
+#[test]
+fn emptied_rows_are_not_stored() {
+    let mut t = Track::new("x", 8);
+    t.set_value_columns(3);
+
+    t.set_note(2, 60);
+    t.set_a(2, 0x10);
+    t.set_value(2, 5, 0.5);
+    assert_eq!(stored_rows(&t), 2);
+
+    t.set_note(2, 0);
+    assert_eq!(stored_rows(&t), 2);
+    t.set_a(2, 0);
+    assert_eq!(stored_rows(&t), 1);
+    assert!(t.patterns[0].get(2).is_none());
+
+    // Removing the value column empties the row:
+    t.set_value_columns(2);
+    assert_eq!(stored_rows(&t), 0);
+    assert_eq!(t.patterns[0], Pattern::new(8));
+}
+
+#[test]
+fn empty_edits_after_the_end_do_not_extend_the_track() {
+    let mut t = Track::new("x", 8);
+    t.set_note(20, 0);
+    assert_eq!(t.line_count(), 8);
+
+    t.set_note(20, 60);
+    assert_eq!(t.line_count(), 24);
+    assert_eq!(t.row_checked(20).unwrap().1.note, 60);
+}
@@ -16,6 +16,9 @@ range 1 100 1 {
 audio_call :op :slaughter "Sl1" g_inst1;
 audio_call :op :audio_send "AS1" g_inst1;
 
+instrument 1 "Slaughter" $[g_inst1] $[];
+groove 1 "Swing" $[0.0, 0.33];
+
 !r = $[:addmul, 0, 1.0, 0.1];
 audio_call :input "AS1" :vol_l r;
 audio_call :input "AS1" :vol_r r;